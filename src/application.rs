@@ -0,0 +1,295 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    sync::Arc,
+};
+
+use space_traders_sdk::{account::Account, agent::Agent, space_traders_client::SpaceTradersClient};
+
+use crate::{
+    config::Config,
+    market_cache::MarketCache,
+    output::OutputFormat,
+    rate_limiter::{self, limited, RateLimiter},
+    survey_store::SurveyStore,
+    utils::enum_name,
+};
+
+/// Requests made by `Agent::new`, which fetches the agent, its ships and its contracts
+pub const AGENT_LOAD_REQUESTS: u32 = 3;
+
+pub struct Application {
+    pub config: Config,
+    pub client: Arc<SpaceTradersClient>,
+    /// Limits requests across the base client and every agent client cloned from it
+    pub limiter: Arc<RateLimiter>,
+    pub account: Account,
+    /// Agents loaded so far this session, filled on first use by `load_agent`
+    pub agents: HashMap<String, Agent>,
+    /// Configured agents that failed to load, with the error that stopped them
+    pub unavailable_agents: HashMap<String, String>,
+    pub surveys: SurveyStore,
+    pub markets: MarketCache,
+    /// Waypoint symbols listed this session keyed by system, offered by REPL completion
+    pub systems: HashMap<String, BTreeSet<String>>,
+    pub output: OutputFormat,
+    /// Reset date the server reported at startup, if it could be reached
+    pub server_reset_date: Option<String>,
+}
+
+impl Application {
+    pub fn new(
+        config: Config,
+        surveys: SurveyStore,
+        markets: MarketCache,
+        output: OutputFormat,
+    ) -> Self {
+        let client = Arc::new(build_client(
+            Some(config.account_token.clone()),
+            &config.base_url,
+        ));
+
+        Application {
+            account: Account::new(client.clone()),
+            config,
+            client,
+            limiter: Arc::new(RateLimiter::new(
+                rate_limiter::REQUESTS_PER_SECOND,
+                rate_limiter::BURST,
+            )),
+            agents: HashMap::new(),
+            unavailable_agents: HashMap::new(),
+            surveys,
+            markets,
+            systems: HashMap::new(),
+            output,
+            server_reset_date: None,
+        }
+    }
+
+    /// Builds an unauthenticated client for the configured server
+    pub fn public_client(&self) -> Arc<SpaceTradersClient> {
+        Arc::new(build_client(None, &self.config.base_url))
+    }
+
+    /// Builds a client authenticated as the given agent, if the callsign is known
+    pub fn agent_client(&self, callsign: &str) -> Option<Arc<SpaceTradersClient>> {
+        let name = self.agent_name(callsign);
+        self.config
+            .agents
+            .iter()
+            .find(|agent| agent.id == name)
+            .map(|agent| {
+                Arc::new(SpaceTradersClient::clone_with_token(
+                    &self.client,
+                    &agent.token,
+                ))
+            })
+    }
+
+    /// The config entry a callsign is stored under, agents added from the environment
+    /// keep their variable name until they are first loaded
+    fn agent_name(&self, callsign: &str) -> String {
+        self.config
+            .added_agent_name(callsign)
+            .unwrap_or_else(|| callsign.to_string())
+    }
+
+    /// Loads a configured agent the first time a command needs it, returning whether it
+    /// is available. Failures are remembered until `agent refresh` is used.
+    pub async fn load_agent(&mut self, callsign: &str) -> bool {
+        if self.agents.contains_key(callsign) {
+            return true;
+        }
+        let name = self.agent_name(callsign);
+        if self.unavailable_agents.contains_key(&name) {
+            return false;
+        }
+        let Some(client) = self.agent_client(&name) else {
+            return false;
+        };
+
+        match limited!(
+            self.limiter,
+            Agent::new(client.clone()),
+            AGENT_LOAD_REQUESTS
+        ) {
+            Ok(agent) => {
+                self.insert_agent(name, agent);
+                self.agents.contains_key(callsign)
+            }
+            Err(e) => {
+                self.unavailable_agents.insert(name, format!("{:?}", e));
+                false
+            }
+        }
+    }
+
+    /// Loads every configured agent that has not been loaded yet, all at once
+    pub async fn load_all_agents(&mut self) {
+        let pending: Vec<_> = self
+            .config
+            .agents
+            .iter()
+            .filter(|agent| {
+                !self.agents.contains_key(&agent.id)
+                    && !self.unavailable_agents.contains_key(&agent.id)
+            })
+            .filter_map(|agent| Some((agent.id.clone(), self.agent_client(&agent.id)?)))
+            .collect();
+
+        let limiter = self.limiter.clone();
+        let limiter = &limiter;
+        let results =
+            futures::future::join_all(pending.into_iter().map(|(id, client)| async move {
+                (
+                    id,
+                    limited!(limiter, Agent::new(client.clone()), AGENT_LOAD_REQUESTS),
+                )
+            }))
+            .await;
+        for (id, result) in results {
+            match result {
+                Ok(agent) => self.insert_agent(id, agent),
+                Err(e) => {
+                    self.unavailable_agents.insert(id, format!("{:?}", e));
+                }
+            }
+        }
+    }
+
+    /// Keeps a loaded agent. Agents only named by environment variables move to the
+    /// callsign the server reports, since variable names cannot hold every callsign.
+    fn insert_agent(&mut self, id: String, agent: Agent) {
+        let callsign = if self.config.added_agents().contains(&id) {
+            let callsign = agent.data.symbol.clone();
+            self.config.rename_added_agent(&id, &callsign);
+            callsign
+        } else {
+            id
+        };
+        self.remember_faction(&agent);
+        self.agents.insert(callsign, agent);
+    }
+
+    /// Stores the starting faction of agents added before factions were kept in the config
+    fn remember_faction(&mut self, agent: &Agent) {
+        let Some(entry) = self
+            .config
+            .agents
+            .iter_mut()
+            .find(|entry| entry.id == agent.data.symbol && entry.faction.is_none())
+        else {
+            return;
+        };
+        entry.faction = Some(enum_name(&agent.data.starting_faction));
+        if let Err(e) = self.config.save() {
+            eprintln!("Error saving config: {}", e);
+        }
+    }
+
+    /// Compares the server's reset date with the one each agent was registered under.
+    /// Agents registered before the latest reset are marked unavailable since their
+    /// tokens no longer work.
+    pub async fn check_server_reset(&mut self) -> anyhow::Result<()> {
+        let status = match limited!(self.limiter, self.client.get_status()) {
+            Ok(status) => status,
+            Err(e) => {
+                eprintln!("Error fetching server status: {:?}", e);
+                return Ok(());
+            }
+        };
+
+        let mut changed = self.config.reset_date.as_ref() != Some(&status.reset_date);
+        let mut stale = Vec::new();
+        // A token from the environment may come from a newer registration than the
+        // stored one, so its agent is left to fail on load if it is stale
+        let overridden: Vec<String> = self
+            .config
+            .agents
+            .iter()
+            .filter(|agent| self.config.token_overridden(&agent.id))
+            .map(|agent| agent.id.clone())
+            .collect();
+        for agent in &mut self.config.agents {
+            if overridden.contains(&agent.id) {
+                continue;
+            }
+            // Agents stored before they kept their own date were valid at the last startup
+            match agent
+                .reset_date
+                .as_ref()
+                .or(self.config.reset_date.as_ref())
+            {
+                Some(date) if *date != status.reset_date => stale.push(agent.id.clone()),
+                _ => {}
+            }
+            if agent.reset_date.is_none() {
+                agent.reset_date = Some(
+                    self.config
+                        .reset_date
+                        .clone()
+                        .unwrap_or_else(|| status.reset_date.clone()),
+                );
+                changed = true;
+            }
+        }
+
+        if !stale.is_empty() {
+            eprintln!(
+                "The server was reset on {}, agents registered before then are stale: {}",
+                status.reset_date,
+                stale.join(", ")
+            );
+            eprintln!("Use `account reset-recover` to re-register them");
+        }
+        for callsign in stale {
+            self.unavailable_agents.insert(
+                callsign,
+                format!(
+                    "Registered before the server reset on {}",
+                    status.reset_date
+                ),
+            );
+        }
+
+        if changed {
+            self.config.reset_date = Some(status.reset_date.clone());
+            self.config.save()?;
+        }
+        self.server_reset_date = Some(status.reset_date);
+        Ok(())
+    }
+
+    /// The loaded agent with a callsign, or an error explaining why there is none
+    pub fn agent(&self, callsign: &str) -> anyhow::Result<&Agent> {
+        self.agents
+            .get(callsign)
+            .ok_or_else(|| self.missing_agent(callsign))
+    }
+
+    pub fn agent_mut(&mut self, callsign: &str) -> anyhow::Result<&mut Agent> {
+        if !self.agents.contains_key(callsign) {
+            return Err(self.missing_agent(callsign));
+        }
+        Ok(self.agents.get_mut(callsign).unwrap())
+    }
+
+    /// Explains why a callsign has no loaded agent
+    fn missing_agent(&self, callsign: &str) -> anyhow::Error {
+        match self.unavailable_agents.get(&self.agent_name(callsign)) {
+            Some(error) => anyhow::anyhow!(
+                "Agent {} could not be loaded: {}\nUse `agent refresh` with a new token or `agent remove`",
+                callsign,
+                error
+            ),
+            None => anyhow::anyhow!("No known agent with that callsign"),
+        }
+    }
+}
+
+fn build_client(token: Option<String>, base_url: &Option<String>) -> SpaceTradersClient {
+    match base_url {
+        Some(base_url) => SpaceTradersClient::with_base_url(token, base_url),
+        None => SpaceTradersClient::new(token),
+    }
+}
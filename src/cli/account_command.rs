@@ -1,7 +1,10 @@
+use std::collections::BTreeMap;
+
 use clap::{Subcommand, ValueEnum};
+use serde_json::json;
 use space_traders_sdk::{account::RegistrationRequest, faction::Factions};
 
-use crate::{config::Agent, Application};
+use crate::{config::Agent, output::render, rate_limiter::limited, utils::enum_name, Application};
 
 #[derive(Subcommand, Debug)]
 pub enum AccountCommand {
@@ -15,31 +18,145 @@ pub enum AccountCommand {
         #[arg(short, long)]
         faction: FactionArg,
     },
+    /// Re-register every stored agent after a server reset, keeping callsigns and factions
+    ResetRecover,
 }
 
 impl AccountCommand {
     pub async fn handle(&self, application: &mut Application) -> anyhow::Result<()> {
         match self {
             AccountCommand::Register { callsign, faction } => {
-                let request: RegistrationRequest = RegistrationRequest {
+                // Built per attempt since a rate limited registration is sent again
+                let request = || RegistrationRequest {
                     callsign: callsign.clone(),
                     faction: Factions::from(faction),
                 };
+                let faction = enum_name(&request().faction);
 
-                match application.account.register_agent(request).await {
+                match limited!(
+                    application.limiter,
+                    application.account.register_agent(request())
+                ) {
                     Ok(agent) => {
                         application.config.agents.push(Agent {
                             id: agent.data.symbol.clone(),
                             token: agent.get_token().unwrap().to_string(),
+                            faction: Some(faction),
+                            reset_date: application.server_reset_date.clone(),
                         });
+                        render(application.output, &agent.data, || {
+                            println!("Successfully registered agent {}", callsign)
+                        })?;
                         application.agents.insert(agent.data.symbol.clone(), agent);
                         application.config.save()?;
-                        println!("Successfully registered agent {}", callsign);
                     }
-                    Err(e) => {
-                        println!("Error: {:?}", e);
-                        return Err(anyhow::anyhow!("{:?}", e));
+                    Err(e) => anyhow::bail!("Failed to register agent: {:?}", e),
+                }
+            }
+            AccountCommand::ResetRecover => {
+                let callsigns: Vec<String> = application
+                    .config
+                    .agents
+                    .iter()
+                    .map(|agent| agent.id.clone())
+                    .collect();
+                let mut skipped = Vec::new();
+                let mut recovered = Vec::new();
+                let mut failed = BTreeMap::new();
+
+                for callsign in callsigns {
+                    // Agents already recovered by an earlier run still load with their token
+                    application.unavailable_agents.remove(&callsign);
+                    let loaded = application.load_agent(&callsign).await;
+                    application.unavailable_agents.remove(&callsign);
+                    let overridden = application.config.token_overridden(&callsign);
+                    // Agents only named by the environment move to their callsign once loaded
+                    let Some(entry) = application
+                        .config
+                        .agents
+                        .iter_mut()
+                        .find(|agent| agent.id == callsign)
+                    else {
+                        skipped.push(callsign);
+                        continue;
+                    };
+                    if loaded {
+                        entry.reset_date = application.server_reset_date.clone();
+                        skipped.push(callsign);
+                        continue;
+                    }
+
+                    // A new token would be lost on save, overridden agents keep their stored
+                    // token and agents only named by the environment are not stored at all
+                    if overridden {
+                        failed.insert(
+                            callsign,
+                            String::from(
+                                "Token is set by the environment, unset it to re-register",
+                            ),
+                        );
+                        continue;
+                    }
+                    let Some(faction) = entry
+                        .faction
+                        .as_ref()
+                        .and_then(|faction| FactionArg::from_str(faction, true).ok())
+                    else {
+                        failed.insert(
+                            callsign,
+                            String::from("Unknown faction, use `account register` instead"),
+                        );
+                        continue;
+                    };
+                    let request = || RegistrationRequest {
+                        callsign: callsign.clone(),
+                        faction: Factions::from(&faction),
+                    };
+
+                    match limited!(
+                        application.limiter,
+                        application.account.register_agent(request())
+                    ) {
+                        Ok(agent) => {
+                            entry.token = agent.get_token().unwrap().to_string();
+                            entry.reset_date = application.server_reset_date.clone();
+                            application.agents.insert(callsign.clone(), agent);
+                            recovered.push(callsign);
+                        }
+                        Err(e) => {
+                            application
+                                .unavailable_agents
+                                .insert(callsign.clone(), format!("{:?}", e));
+                            failed.insert(callsign, format!("{:?}", e));
+                        }
+                    }
+                }
+
+                application.config.save()?;
+
+                let summary = json!({
+                    "skipped": skipped,
+                    "recovered": recovered,
+                    "failed": failed,
+                });
+                render(application.output, &summary, || {
+                    for callsign in &skipped {
+                        println!("Agent {} is still valid, skipping", callsign);
+                    }
+                    for callsign in &recovered {
+                        println!("Re-registered agent {}", callsign);
                     }
+                })?;
+                if !failed.is_empty() {
+                    let reasons: Vec<_> = failed
+                        .iter()
+                        .map(|(callsign, error)| format!("{}: {}", callsign, error))
+                        .collect();
+                    anyhow::bail!(
+                        "{} agents could not be recovered\n{}",
+                        failed.len(),
+                        reasons.join("\n")
+                    );
                 }
             }
         }
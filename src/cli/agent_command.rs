@@ -1,18 +1,49 @@
+use std::sync::Arc;
+
 use clap::Subcommand;
-use space_traders_sdk::agent::AgentData;
+use serde_json::json;
+use space_traders_sdk::{
+    agent::{Agent, AgentData},
+    space_traders_client::SpaceTradersClient,
+};
 
-use crate::Application;
+use crate::{
+    application::AGENT_LOAD_REQUESTS,
+    output::render,
+    rate_limiter::limited,
+    table::{Table, TableArgs},
+    utils::enum_name,
+    Application,
+};
 
 #[derive(Subcommand, Debug)]
 pub enum AgentCommand {
-    // List known Agents
-    ListAgents,
+    /// List known Agents
+    ListAgents {
+        #[command(flatten)]
+        table: TableArgs,
+    },
     /// Show information for a given agent
     Info {
         /// Callsign of the agent
         #[arg(short, long)]
         callsign: String,
     },
+    /// Reload an agent, optionally replacing its stored token
+    Refresh {
+        /// Callsign of the agent
+        #[arg(short, long)]
+        callsign: String,
+        /// New token to store for the agent
+        #[arg(short, long)]
+        token: Option<String>,
+    },
+    /// Remove an agent from the config
+    Remove {
+        /// Callsign of the agent
+        #[arg(short, long)]
+        callsign: String,
+    },
 }
 
 pub fn print_agent_data(agent_data: &AgentData) {
@@ -33,16 +64,113 @@ pub fn print_agent_data(agent_data: &AgentData) {
 impl AgentCommand {
     pub async fn handle(&self, application: &mut Application) -> anyhow::Result<()> {
         match self {
-            AgentCommand::ListAgents => application
-                .agents
-                .iter()
-                .for_each(|(callsign, _)| println!("{}", callsign)),
-            AgentCommand::Info { callsign } => match application.agents.get(callsign) {
-                Some(agent) => print_agent_data(&agent.data),
-                None => {
-                    println!("No known agent with that callsign");
+            AgentCommand::ListAgents { table } => {
+                table.check(application.output)?;
+                application.load_all_agents().await;
+                let agents: Vec<_> = application.agents.values().collect();
+                let mut rows = Table::new(&[
+                    "callsign",
+                    "status",
+                    "headquarters",
+                    "credits",
+                    "faction",
+                    "ships",
+                ]);
+                for agent in &agents {
+                    rows.row(vec![
+                        agent.data.symbol.clone(),
+                        String::from("available"),
+                        agent.data.headquarters.clone(),
+                        agent.data.credits.to_string(),
+                        enum_name(&agent.data.starting_faction),
+                        agent.ships().count().to_string(),
+                    ]);
+                }
+                for (callsign, error) in &application.unavailable_agents {
+                    let mut row = vec![callsign.clone(), format!("unavailable: {}", error)];
+                    row.resize(6, String::from("-"));
+                    rows.row(row);
+                }
+                let text = rows.format(table)?;
+                let data: Vec<_> = agents.iter().map(|agent| &agent.data).collect();
+                let list = json!({
+                    "agents": data,
+                    "unavailable": application.unavailable_agents,
+                });
+                render(application.output, &list, || print!("{}", text))?;
+            }
+            AgentCommand::Info { callsign } => {
+                application.load_agent(callsign).await;
+                let agent = application.agent(callsign)?;
+                render(application.output, &agent.data, || {
+                    print_agent_data(&agent.data)
+                })?;
+            }
+            AgentCommand::Refresh { callsign, token } => {
+                let Some(entry) = application
+                    .config
+                    .agents
+                    .iter_mut()
+                    .find(|agent| &agent.id == callsign)
+                else {
+                    anyhow::bail!("No known agent with that callsign");
+                };
+                // A new token is only stored once an agent has loaded with it
+                let client = Arc::new(SpaceTradersClient::clone_with_token(
+                    &application.client,
+                    token.as_ref().unwrap_or(&entry.token),
+                ));
+
+                match limited!(
+                    application.limiter,
+                    Agent::new(client.clone()),
+                    AGENT_LOAD_REQUESTS
+                ) {
+                    Ok(agent) => {
+                        render(application.output, &agent.data, || {
+                            println!("Reloaded agent {}", callsign);
+                            print_agent_data(&agent.data);
+                        })?;
+                        application.unavailable_agents.remove(callsign);
+                        application.agents.insert(callsign.clone(), agent);
+                        if let Some(token) = token {
+                            entry.token = token.clone();
+                            application.config.save()?;
+                        }
+                    }
+                    // A rejected new token leaves the agent as it was with its stored token
+                    Err(e) if token.is_some() => {
+                        anyhow::bail!(
+                            "Failed to load agent {} with the new token: {:?}",
+                            callsign,
+                            e
+                        );
+                    }
+                    Err(e) => {
+                        application.agents.remove(callsign);
+                        application
+                            .unavailable_agents
+                            .insert(callsign.clone(), format!("{:?}", e));
+                        anyhow::bail!("Failed to load agent {}: {:?}", callsign, e);
+                    }
+                }
+            }
+            AgentCommand::Remove { callsign } => {
+                let before = application.config.agents.len();
+                application
+                    .config
+                    .agents
+                    .retain(|agent| &agent.id != callsign);
+                if application.config.agents.len() == before {
+                    anyhow::bail!("No known agent with that callsign");
                 }
-            },
+                application.agents.remove(callsign);
+                application.unavailable_agents.remove(callsign);
+                application.config.save()?;
+                render(application.output, &json!({ "removed": callsign }), || {
+                    println!("Removed agent {}", callsign)
+                })?;
+            }
         }
 
         Ok(())
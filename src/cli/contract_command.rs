@@ -1,7 +1,21 @@
+use std::sync::Arc;
+
+use chrono::{DateTime, Utc};
 use clap::Subcommand;
-use space_traders_sdk::contract::ContractData;
+use serde::Serialize;
+use space_traders_sdk::{
+    agent::Agent, contract::ContractData, market::TradeSymbol, ship::ShipFlightMode,
+    space_traders_client::SpaceTradersClient, system::System,
+};
 
-use crate::Application;
+use crate::{
+    output::render,
+    rate_limiter::{limited, RateLimiter},
+    table::{Table, TableArgs},
+    travel::{distance, format_duration, fuel_required, system_symbol, travel_seconds},
+    utils::{enum_name, parse_trade_symbol},
+    Application,
+};
 
 #[derive(Subcommand, Debug)]
 pub enum ContractCommand {
@@ -10,6 +24,8 @@ pub enum ContractCommand {
         /// Callsign of the agent
         #[arg(short, long)]
         callsign: String,
+        #[command(flatten)]
+        table: TableArgs,
     },
     /// Show info for a contract for a given agent
     Info {
@@ -29,6 +45,51 @@ pub enum ContractCommand {
         #[arg(short, long)]
         id: String,
     },
+    /// Estimate the cost, travel and profit of completing a contract
+    Evaluate {
+        /// Callsign of the agent
+        #[arg(short, long)]
+        callsign: String,
+        /// The ID of the contract
+        #[arg(short, long)]
+        id: String,
+    },
+    /// Deliver cargo from a ship towards a contract
+    Deliver {
+        /// Callsign of the agent
+        #[arg(short, long)]
+        callsign: String,
+        /// The ID of the contract
+        #[arg(short, long)]
+        id: String,
+        /// Symbol of the ship holding the cargo
+        #[arg(short, long)]
+        ship: String,
+        /// Trade symbol of the good to deliver
+        #[arg(short, long, value_parser = parse_trade_symbol)]
+        good: TradeSymbol,
+        /// Number of units to deliver
+        #[arg(short, long)]
+        units: u32,
+    },
+    /// Fulfill a contract once all deliveries are made
+    Fulfill {
+        /// Callsign of the agent
+        #[arg(short, long)]
+        callsign: String,
+        /// The ID of the contract
+        #[arg(short, long)]
+        id: String,
+    },
+    /// Negotiate a new contract using a ship docked at a faction waypoint
+    Negotiate {
+        /// Callsign of the agent
+        #[arg(short, long)]
+        callsign: String,
+        /// Symbol of the ship to negotiate with
+        #[arg(short, long)]
+        ship: String,
+    },
 }
 
 fn display_contract(contract: &ContractData) {
@@ -71,56 +132,464 @@ fn display_contract(contract: &ContractData) {
     println!();
 }
 
-fn display_contract_short(contract: &ContractData) {
+fn contracts_table(contracts: &[&ContractData]) -> Table {
+    let mut table = Table::new(&[
+        "id",
+        "type",
+        "faction",
+        "payment",
+        "deadline",
+        "accepted",
+        "fulfilled",
+    ]);
+    for contract in contracts {
+        table.row(vec![
+            contract.id.clone(),
+            enum_name(&contract.contract_type),
+            enum_name(&contract.faction),
+            (contract.terms.payment.on_accepted as u64
+                + contract.terms.payment.on_fulfilled as u64)
+                .to_string(),
+            contract.terms.deadline.to_string(),
+            String::from(if contract.accepted { "yes" } else { "no" }),
+            String::from(if contract.fulfilled { "yes" } else { "no" }),
+        ]);
+    }
+    table
+}
+
+/// Looks up a contract of an agent, `edit_contract` panics on an unknown ID
+fn find_contract<'a>(agent: &'a Agent, id: &str) -> anyhow::Result<&'a ContractData> {
+    match agent
+        .contracts()
+        .find(|(contract_id, _)| *contract_id == id)
+    {
+        Some((_, contract)) => Ok(&contract.data),
+        None => anyhow::bail!("No known contract with that ID"),
+    }
+}
+
+async fn waypoint_position(
+    limiter: &RateLimiter,
+    client: &Arc<SpaceTradersClient>,
+    waypoint: &str,
+) -> anyhow::Result<(i32, i32)> {
+    let system = System::new(client.clone(), system_symbol(waypoint));
+    let waypoint = limited!(limiter, system.get_waypoint(waypoint))
+        .map_err(|e| anyhow::anyhow!("Failed to look up waypoint {}: {}", waypoint, e))?;
+    Ok((waypoint.data.x, waypoint.data.y))
+}
+
+#[derive(Serialize)]
+struct DeliverableEstimate {
+    good: TradeSymbol,
+    remaining: u32,
+    /// Why no estimate could be made for the remaining units
+    unknown: Option<String>,
+    source: Option<String>,
+    price_per_unit: Option<u32>,
+    purchase_cost: u64,
+    trips: u64,
+    fuel: u64,
+    travel_seconds: u64,
+}
+
+#[derive(Serialize)]
+struct ContractEvaluation {
+    contract_id: String,
+    hauler: String,
+    hauler_capacity: u32,
+    hauler_speed: u32,
+    deliverables: Vec<DeliverableEstimate>,
+    payment: u64,
+    purchase_cost: u64,
+    fuel: u64,
+    fuel_cost: Option<u64>,
+    travel_seconds: u64,
+    estimated_profit: i64,
+    complete: bool,
+    deadline: String,
+    estimated_finish: String,
+    deadline_met: Option<bool>,
+}
+
+/// Estimates a contract using cached market prices and the agent's largest cargo hold,
+/// assuming the hauler cruises from where it is to the cheapest market in the delivery
+/// system, then between that market and the destination until every unit is delivered
+async fn evaluate_contract(
+    application: &Application,
+    callsign: &str,
+    id: &str,
+) -> anyhow::Result<ContractEvaluation> {
+    let agent = application.agent(callsign)?;
+    let contract = find_contract(agent, id)?;
+    let Some(hauler) = agent
+        .ships()
+        .map(|(_, ship)| &ship.data)
+        .filter(|ship| ship.cargo.capacity > 0)
+        .max_by_key(|ship| ship.cargo.capacity)
+    else {
+        anyhow::bail!("No ship with cargo space to haul with");
+    };
+    let client = application
+        .agent_client(callsign)
+        .unwrap_or_else(|| application.public_client());
+    let mode = ShipFlightMode::Cruise;
+    let destination = &hauler.nav.route.destination;
+    // Where the hauler is before each deliverable, it ends up at the previous destination
+    let mut position = (
+        hauler.nav.system_symbol.clone(),
+        Ok((destination.x, destination.y)),
+    );
+
+    let mut deliverables = Vec::new();
+    for deliverable in contract.terms.deliver.iter().flatten() {
+        let remaining = deliverable
+            .units_required
+            .saturating_sub(deliverable.units_fulfilled);
+        let mut estimate = DeliverableEstimate {
+            good: deliverable.trade_symbol.clone(),
+            remaining,
+            unknown: None,
+            source: None,
+            price_per_unit: None,
+            purchase_cost: 0,
+            trips: 0,
+            fuel: 0,
+            travel_seconds: 0,
+        };
+        if remaining == 0 {
+            deliverables.push(estimate);
+            continue;
+        }
+
+        let system = system_symbol(&deliverable.destination_symbol);
+        let start = std::mem::replace(
+            &mut position,
+            (
+                system.to_string(),
+                waypoint_position(
+                    &application.limiter,
+                    &client,
+                    &deliverable.destination_symbol,
+                )
+                .await,
+            ),
+        );
+        let Some((source, trade_good)) = application
+            .markets
+            .cheapest_purchase(&deliverable.trade_symbol, system)
+        else {
+            estimate.unknown = Some(format!("no cached market in {} sells it", system));
+            deliverables.push(estimate);
+            continue;
+        };
+        estimate.source = Some(source.to_string());
+        estimate.price_per_unit = Some(trade_good.purchase_price);
+        estimate.purchase_cost = remaining as u64 * trade_good.purchase_price as u64;
+
+        if start.0 != system {
+            estimate.unknown = Some(format!(
+                "the hauler is in {}, reaching {} needs jumps",
+                start.0, system
+            ));
+            deliverables.push(estimate);
+            continue;
+        }
+        let (approach, leg) = match (
+            start.1,
+            waypoint_position(&application.limiter, &client, source).await,
+            &position.1,
+        ) {
+            (Ok(start), Ok(source), Ok(destination)) => {
+                (distance(start, source), distance(source, *destination))
+            }
+            (Err(e), _, _) | (_, Err(e), _) => {
+                estimate.unknown = Some(e.to_string());
+                deliverables.push(estimate);
+                continue;
+            }
+            (_, _, Err(e)) => {
+                estimate.unknown = Some(e.to_string());
+                deliverables.push(estimate);
+                continue;
+            }
+        };
+
+        (estimate.trips, estimate.fuel, estimate.travel_seconds) = haul(
+            remaining,
+            hauler.cargo.capacity,
+            hauler.engine.speed,
+            approach,
+            leg,
+            &mode,
+        );
+        deliverables.push(estimate);
+    }
+
+    let purchase_cost = deliverables.iter().map(|d| d.purchase_cost).sum();
+    let fuel = deliverables.iter().map(|d| d.fuel).sum::<u64>();
+    let seconds = deliverables.iter().map(|d| d.travel_seconds).sum();
+    // Markets sell fuel in units that each fill 100 units of a ship's tank
+    let fuel_cost = application
+        .markets
+        .cheapest_purchase(&TradeSymbol::Fuel, &hauler.nav.system_symbol)
+        .map(|(_, trade_good)| fuel.div_ceil(100) * trade_good.purchase_price as u64);
+    let payment =
+        contract.terms.payment.on_accepted as u64 + contract.terms.payment.on_fulfilled as u64;
+    let finish = Utc::now() + chrono::Duration::seconds(seconds as i64);
+
+    Ok(ContractEvaluation {
+        contract_id: contract.id.clone(),
+        hauler: hauler.symbol.clone(),
+        hauler_capacity: hauler.cargo.capacity,
+        hauler_speed: hauler.engine.speed,
+        complete: deliverables.iter().all(|d| d.unknown.is_none()),
+        deliverables,
+        payment,
+        purchase_cost,
+        fuel,
+        fuel_cost,
+        travel_seconds: seconds,
+        estimated_profit: payment as i64 - purchase_cost as i64 - fuel_cost.unwrap_or(0) as i64,
+        deadline: contract.terms.deadline.to_string(),
+        estimated_finish: finish.to_rfc3339(),
+        deadline_met: deadline_met(&contract.terms.deadline, finish),
+    })
+}
+
+/// Trips, fuel and seconds needed to haul units with a ship of the given cargo capacity
+/// and engine speed. One leg goes out to the market, then a full leg per trip and a
+/// return leg between trips.
+fn haul(
+    remaining: u32,
+    capacity: u32,
+    speed: u32,
+    approach: f64,
+    leg: f64,
+    mode: &ShipFlightMode,
+) -> (u64, u64, u64) {
+    let trips = remaining.div_ceil(capacity) as u64;
+    let legs = 2 * trips - 1;
+    let fuel = fuel_required(approach, mode) as u64 + fuel_required(leg, mode) as u64 * legs;
+    let seconds = travel_seconds(approach, speed, mode) + travel_seconds(leg, speed, mode) * legs;
+    (trips, fuel, seconds)
+}
+
+/// Whether finishing at the given time meets a deadline, unknown if it cannot be read
+fn deadline_met(deadline: &str, finish: DateTime<Utc>) -> Option<bool> {
+    DateTime::parse_from_rfc3339(deadline)
+        .ok()
+        .map(|deadline| finish <= deadline.with_timezone(&Utc))
+}
+
+fn display_evaluation(evaluation: &ContractEvaluation) {
+    println!("");
+    println!("Evaluation of contract {}", evaluation.contract_id);
+    println!("------------------------------");
     println!(
-        "ID: {} | Type: {} | Faction: {} | Accepted: {} | Fulfilled: {}",
-        contract.id,
-        serde_json::to_string(&contract.contract_type).unwrap(),
-        serde_json::to_string(&contract.faction).unwrap(),
-        if contract.accepted { "yes" } else { "no" },
-        if contract.fulfilled { "yes" } else { "no" }
+        "Hauler: {} ({} cargo, {} speed)",
+        evaluation.hauler, evaluation.hauler_capacity, evaluation.hauler_speed
+    );
+    println!("Deliverables:");
+    for deliverable in &evaluation.deliverables {
+        let good = serde_json::to_string(&deliverable.good).unwrap();
+        match (
+            &deliverable.unknown,
+            &deliverable.source,
+            deliverable.price_per_unit,
+        ) {
+            _ if deliverable.remaining == 0 => println!("  {} already delivered", good),
+            (Some(unknown), _, _) => {
+                println!("  {} {}: unknown, {}", deliverable.remaining, good, unknown)
+            }
+            (None, Some(source), Some(price)) => println!(
+                "  {} {} from {} at {} each: {} credits, {} trips, {} fuel, {}",
+                deliverable.remaining,
+                good,
+                source,
+                price,
+                deliverable.purchase_cost,
+                deliverable.trips,
+                deliverable.fuel,
+                format_duration(deliverable.travel_seconds)
+            ),
+            _ => println!("  {} {}: unknown", deliverable.remaining, good),
+        }
+    }
+
+    println!("Payment: {}", evaluation.payment);
+    println!("Purchase Cost: {}", evaluation.purchase_cost);
+    match evaluation.fuel_cost {
+        Some(fuel_cost) => println!(
+            "Fuel: {} units, about {} credits",
+            evaluation.fuel, fuel_cost
+        ),
+        None => println!("Fuel: {} units, no cached fuel price", evaluation.fuel),
+    }
+    println!(
+        "Travel Time: {}",
+        format_duration(evaluation.travel_seconds)
     );
+    println!(
+        "Estimated Profit: {}{}",
+        evaluation.estimated_profit,
+        if evaluation.complete {
+            ""
+        } else {
+            " (incomplete, some goods could not be estimated)"
+        }
+    );
+    match evaluation.deadline_met {
+        Some(true) => println!("Deadline: {} can be met", evaluation.deadline),
+        Some(false) => println!(
+            "Deadline: {} WARNING: cannot be met, estimated finish {}",
+            evaluation.deadline, evaluation.estimated_finish
+        ),
+        None => println!("Deadline: could not read {}", evaluation.deadline),
+    }
+    println!();
 }
 
 impl ContractCommand {
     pub async fn handle(&self, application: &mut Application) -> anyhow::Result<()> {
+        let (ContractCommand::List { callsign, .. }
+        | ContractCommand::Info { callsign, .. }
+        | ContractCommand::Accept { callsign, .. }
+        | ContractCommand::Evaluate { callsign, .. }
+        | ContractCommand::Deliver { callsign, .. }
+        | ContractCommand::Fulfill { callsign, .. }
+        | ContractCommand::Negotiate { callsign, .. }) = self;
+        application.load_agent(callsign).await;
+
+        let output = application.output;
+        let limiter = application.limiter.clone();
+
         match self {
-            ContractCommand::List { callsign } => match application.agents.get(callsign) {
-                Some(agent) => {
-                    agent
-                        .contracts()
-                        .for_each(|f| display_contract_short(&f.1.data));
-                }
-                None => {
-                    println!("No known agent with that callsign");
+            ContractCommand::List { callsign, table } => {
+                table.check(output)?;
+                let agent = application.agent(callsign)?;
+                let contracts: Vec<_> = agent.contracts().map(|f| &f.1.data).collect();
+                let text = contracts_table(&contracts).format(table)?;
+                render(output, &contracts, || print!("{}", text))?;
+            }
+            ContractCommand::Info { callsign, id } => {
+                let contract = find_contract(application.agent(callsign)?, id)?;
+                render(output, contract, || display_contract(contract))?;
+            }
+            ContractCommand::Accept { callsign, id } => {
+                let agent = application.agent_mut(callsign)?;
+                find_contract(agent, id)?;
+                let contract = agent.edit_contract(&id);
+                if let Err(e) = limited!(limiter, contract.accept()) {
+                    anyhow::bail!("Failed to accept contract: {:?}", e);
                 }
-            },
-            ContractCommand::Info { callsign, id } => match application.agents.get_mut(callsign) {
-                Some(agent) => display_contract(&agent.edit_contract(&id).data),
-                None => {
-                    println!("No known agent with that callsign");
+                render(output, &contract.data, || {
+                    println!("Contract accepted: {:?}", contract.data)
+                })?;
+            }
+            ContractCommand::Evaluate { callsign, id } => {
+                let evaluation = evaluate_contract(application, callsign, id).await?;
+                render(output, &evaluation, || display_evaluation(&evaluation))?;
+            }
+            ContractCommand::Deliver {
+                callsign,
+                id,
+                ship,
+                good,
+                units,
+            } => {
+                let agent = application.agent_mut(callsign)?;
+                if !agent.ships().any(|(symbol, _)| symbol == ship) {
+                    anyhow::bail!("No known ship with that symbol");
                 }
-            },
-            ContractCommand::Accept { callsign, id } => {
-                match application.agents.get_mut(callsign) {
-                    Some(agent) => {
-                        let contract = agent.edit_contract(&id);
-                        match contract.accept().await {
-                            Ok(_) => {
-                                println!("Contract accepted: {:?}", contract.data);
-                            }
-                            Err(e) => {
-                                println!("Failed to accept contract: {:?}", e);
-                            }
-                        }
-                    }
-                    None => {
-                        println!("No known agent with that callsign");
-                    }
+                find_contract(agent, id)?;
+                let contract = agent.edit_contract(&id);
+                let delivery = match limited!(limiter, contract.deliver(ship, good.clone(), *units))
+                {
+                    Ok(delivery) => delivery,
+                    Err(e) => anyhow::bail!("Failed to deliver cargo: {:?}", e),
+                };
+                render(output, &contract.data, || {
+                    println!(
+                        "Delivered {} {}",
+                        units,
+                        serde_json::to_string(good).unwrap()
+                    );
+                    display_contract(&contract.data);
+                })?;
+                agent.edit_ship(ship).data.cargo = delivery.cargo;
+            }
+            ContractCommand::Fulfill { callsign, id } => {
+                let agent = application.agent_mut(callsign)?;
+                find_contract(agent, id)?;
+                let fulfilled = match limited!(limiter, agent.edit_contract(&id).fulfill()) {
+                    Ok(fulfilled) => fulfilled,
+                    Err(e) => anyhow::bail!("Failed to fulfill contract: {:?}", e),
+                };
+                render(output, &fulfilled, || {
+                    println!("Contract fulfilled: {}", fulfilled.contract.id);
+                    println!("Credits: {}", fulfilled.agent.credits);
+                })?;
+                agent.data = fulfilled.agent;
+            }
+            ContractCommand::Negotiate { callsign, ship } => {
+                let agent = application.agent_mut(callsign)?;
+                if !agent.ships().any(|(symbol, _)| symbol == ship) {
+                    anyhow::bail!("No known ship with that symbol");
                 }
+                let contract = match limited!(limiter, agent.edit_ship(ship).negotiate_contract()) {
+                    Ok(contract) => contract,
+                    Err(e) => anyhow::bail!("Failed to negotiate contract: {:?}", e),
+                };
+                render(output, &contract, || display_contract(&contract))?;
+                agent.add_contract(contract);
             }
         }
 
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(time: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(time)
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    #[test]
+    fn hauls_take_a_round_trip_per_load_after_the_approach() {
+        // 100 units in a 40 unit hold is three loads: out, back, out, back, out
+        let (trips, fuel, seconds) = haul(100, 40, 10, 0.0, 10.0, &ShipFlightMode::Cruise);
+        assert_eq!(trips, 3);
+        assert_eq!(fuel, 1 + 10 * 5);
+        assert_eq!(seconds, 18 + 40 * 5);
+
+        let (trips, fuel, seconds) = haul(40, 40, 10, 5.0, 10.0, &ShipFlightMode::Cruise);
+        assert_eq!(trips, 1);
+        assert_eq!(fuel, 5 + 10);
+        assert_eq!(seconds, 28 + 40);
+    }
+
+    #[test]
+    fn deadlines_are_met_when_finishing_by_them() {
+        let deadline = "2024-01-02T00:00:00.000Z";
+        assert_eq!(
+            deadline_met(deadline, at("2024-01-01T23:00:00Z")),
+            Some(true)
+        );
+        assert_eq!(
+            deadline_met(deadline, at("2024-01-02T00:00:00Z")),
+            Some(true)
+        );
+        assert_eq!(
+            deadline_met(deadline, at("2024-01-02T01:00:00Z")),
+            Some(false)
+        );
+        assert_eq!(deadline_met("whenever", at("2024-01-01T00:00:00Z")), None);
+    }
+}
@@ -1,16 +1,23 @@
+use std::path::PathBuf;
+
 use clap::{Parser, Subcommand};
 
 use crate::{
     cli::{
         account_command::AccountCommand, agent_command::AgentCommand,
-        contract_command::ContractCommand, system_command::SystemCommand,
+        contract_command::ContractCommand, ship_command::ShipCommand,
+        survey_command::SurveyCommand, system_command::SystemCommand,
     },
+    output::OutputFormat,
+    repl::{run_script, Session},
     Application,
 };
 
 mod account_command;
 mod agent_command;
 mod contract_command;
+mod ship_command;
+mod survey_command;
 mod system_command;
 
 #[derive(Subcommand, Debug)]
@@ -30,16 +37,35 @@ pub enum Commands {
         #[command(subcommand)]
         command: ContractCommand,
     },
+    /// Ship level commands
+    Ship {
+        #[command(subcommand)]
+        command: ShipCommand,
+    },
+    /// Stored survey commands
+    Survey {
+        #[command(subcommand)]
+        command: SurveyCommand,
+    },
     /// System level commands
     System {
         #[command(subcommand)]
         command: SystemCommand,
     },
+    /// Run a script of REPL commands, one per line
+    Run {
+        /// Path of the script file
+        file: PathBuf,
+    },
 }
 
 #[derive(Parser, Debug)]
 #[command(name = "repl")]
 pub struct ReplCli {
+    /// Format to print command results in, defaults to the session format
+    #[arg(long, global = true, value_enum)]
+    pub output: Option<OutputFormat>,
+
     #[command(subcommand)]
     pub command: Option<Commands>,
 }
@@ -49,6 +75,13 @@ pub async fn handle_command(cmd: Commands, application: &mut Application) -> any
         Commands::Account { command } => command.handle(application).await,
         Commands::Agent { command } => command.handle(application).await,
         Commands::Contract { command } => command.handle(application).await,
+        Commands::Ship { command } => command.handle(application).await,
+        Commands::Survey { command } => command.handle(application).await,
         Commands::System { command } => command.handle(application).await,
+        Commands::Run { file } => {
+            let mut session = Session::default();
+            Box::pin(run_script(application, &mut session, &file)).await?;
+            Ok(())
+        }
     }
 }
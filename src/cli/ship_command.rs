@@ -0,0 +1,821 @@
+use std::io::{self, Write};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use clap::{Subcommand, ValueEnum};
+use serde_json::json;
+use space_traders_sdk::{
+    agent::AgentData,
+    market::{MarketTransaction, TradeSymbol},
+    ship::{
+        Cooldown, RefineProduce, Ship, ShipCargo, ShipData, ShipFlightMode, ShipNav, ShipNavStatus,
+    },
+    survey::Survey,
+    system::System,
+};
+
+use crate::{
+    output::{render, OutputFormat},
+    rate_limiter::limited,
+    travel::{distance, fuel_required},
+    utils::parse_trade_symbol,
+    Application,
+};
+
+#[derive(Subcommand, Debug)]
+pub enum ShipCommand {
+    /// List the ships in an agent's fleet
+    List {
+        /// Callsign of the agent
+        #[arg(short, long)]
+        callsign: String,
+    },
+    /// Show info for a ship in an agent's fleet
+    Info {
+        /// Callsign of the agent
+        #[arg(short, long)]
+        callsign: String,
+        /// Symbol of the ship
+        #[arg(short, long)]
+        ship: String,
+    },
+    /// Move a ship into orbit
+    Orbit {
+        /// Callsign of the agent
+        #[arg(short, long)]
+        callsign: String,
+        /// Symbol of the ship
+        #[arg(short, long)]
+        ship: String,
+    },
+    /// Dock a ship at its current waypoint
+    Dock {
+        /// Callsign of the agent
+        #[arg(short, long)]
+        callsign: String,
+        /// Symbol of the ship
+        #[arg(short, long)]
+        ship: String,
+    },
+    /// Navigate a ship to a waypoint in its current system
+    Navigate {
+        /// Callsign of the agent
+        #[arg(short, long)]
+        callsign: String,
+        /// Symbol of the ship
+        #[arg(short, long)]
+        ship: String,
+        /// Symbol of the destination waypoint
+        #[arg(short, long)]
+        to: String,
+    },
+    /// Wait until a ship in transit arrives at its destination
+    WaitArrival {
+        /// Callsign of the agent
+        #[arg(short, long)]
+        callsign: String,
+        /// Symbol of the ship
+        #[arg(short, long)]
+        ship: String,
+    },
+    /// Survey the current waypoint for resource deposits
+    Survey {
+        /// Callsign of the agent
+        #[arg(short, long)]
+        callsign: String,
+        /// Symbol of the ship
+        #[arg(short, long)]
+        ship: String,
+    },
+    /// Extract resources at the current waypoint
+    Extract {
+        /// Callsign of the agent
+        #[arg(short, long)]
+        callsign: String,
+        /// Symbol of the ship
+        #[arg(short, long)]
+        ship: String,
+        /// Signature of a survey to target the extraction with
+        #[arg(long)]
+        survey: Option<String>,
+    },
+    /// Siphon gases at the current waypoint
+    Siphon {
+        /// Callsign of the agent
+        #[arg(short, long)]
+        callsign: String,
+        /// Symbol of the ship
+        #[arg(short, long)]
+        ship: String,
+    },
+    /// Refine raw materials in cargo into a processed good
+    Refine {
+        /// Callsign of the agent
+        #[arg(short, long)]
+        callsign: String,
+        /// Symbol of the ship
+        #[arg(short, long)]
+        ship: String,
+        /// Good to produce
+        #[arg(short, long)]
+        produce: RefineProduceArg,
+    },
+    /// Jettison cargo into space
+    Jettison {
+        /// Callsign of the agent
+        #[arg(short, long)]
+        callsign: String,
+        /// Symbol of the ship
+        #[arg(short, long)]
+        ship: String,
+        /// Trade symbol of the good to jettison
+        #[arg(short, long, value_parser = parse_trade_symbol)]
+        good: TradeSymbol,
+        /// Number of units to jettison
+        #[arg(short, long)]
+        units: u32,
+    },
+    /// Buy goods from the market at the current waypoint
+    Buy {
+        /// Callsign of the agent
+        #[arg(short, long)]
+        callsign: String,
+        /// Symbol of the ship
+        #[arg(short, long)]
+        ship: String,
+        /// Trade symbol of the good to buy
+        #[arg(short, long, value_parser = parse_trade_symbol)]
+        good: TradeSymbol,
+        /// Number of units to buy
+        #[arg(short, long)]
+        units: u32,
+    },
+    /// Sell cargo to the market at the current waypoint
+    Sell {
+        /// Callsign of the agent
+        #[arg(short, long)]
+        callsign: String,
+        /// Symbol of the ship
+        #[arg(short, long)]
+        ship: String,
+        /// Trade symbol of the good to sell
+        #[arg(short, long, value_parser = parse_trade_symbol)]
+        good: TradeSymbol,
+        /// Number of units to sell
+        #[arg(
+            short,
+            long,
+            required_unless_present = "all",
+            conflicts_with = "all",
+            value_parser = clap::value_parser!(u32).range(1..)
+        )]
+        units: Option<u32>,
+        /// Sell every unit of the good in cargo
+        #[arg(long)]
+        all: bool,
+    },
+    /// Transfer cargo to another ship at the same waypoint
+    Transfer {
+        /// Callsign of the agent
+        #[arg(short, long)]
+        callsign: String,
+        /// Symbol of the ship
+        #[arg(short, long)]
+        ship: String,
+        /// Symbol of the ship receiving the cargo
+        #[arg(short, long)]
+        to_ship: String,
+        /// Trade symbol of the good to transfer
+        #[arg(short, long, value_parser = parse_trade_symbol)]
+        good: TradeSymbol,
+        /// Number of units to transfer
+        #[arg(short, long)]
+        units: u32,
+    },
+    /// Set the flight mode of a ship
+    FlightMode {
+        /// Callsign of the agent
+        #[arg(short, long)]
+        callsign: String,
+        /// Symbol of the ship
+        #[arg(short, long)]
+        ship: String,
+        /// Flight mode to use for future navigation
+        mode: FlightModeArg,
+    },
+}
+
+/// Looks up a ship in an agent's fleet, failing when either is unknown
+fn find_ship<'a>(
+    application: &'a mut Application,
+    callsign: &str,
+    ship: &str,
+) -> anyhow::Result<&'a mut Ship> {
+    let agent = application.agent_mut(callsign)?;
+    if !agent.ships().any(|(symbol, _)| symbol == ship) {
+        anyhow::bail!("No known ship with that symbol");
+    }
+    Ok(agent.edit_ship(ship))
+}
+
+/// Counts down once a second until the given arrival time has passed
+async fn countdown(arrival: DateTime<Utc>, show: bool) {
+    let mut interval = tokio::time::interval(Duration::from_secs(1));
+    loop {
+        interval.tick().await;
+        let remaining = (arrival - Utc::now()).num_seconds();
+        if remaining <= 0 {
+            break;
+        }
+        if show {
+            print!("\rArriving in {:02}:{:02} ", remaining / 60, remaining % 60);
+            io::stdout().flush().unwrap();
+        }
+    }
+    if show {
+        println!();
+    }
+}
+
+fn display_nav(nav: &ShipNav) {
+    println!(
+        "Status: {} at {} ({})",
+        serde_json::to_string(&nav.status).unwrap(),
+        nav.waypoint_symbol,
+        serde_json::to_string(&nav.flight_mode).unwrap()
+    );
+}
+
+/// Stores the agent data returned by a transaction so the new balance is kept
+fn update_agent(application: &mut Application, callsign: &str, data: AgentData) {
+    if let Some(agent) = application.agents.get_mut(callsign) {
+        agent.data = data;
+    }
+}
+
+fn display_transaction(verb: &str, transaction: &MarketTransaction) {
+    println!(
+        "{} {} {} for {} credits ({} each)",
+        verb,
+        transaction.units,
+        serde_json::to_string(&transaction.trade_symbol).unwrap(),
+        transaction.total_price,
+        transaction.price_per_unit
+    );
+}
+
+fn display_cargo(cargo: &ShipCargo) {
+    println!("Cargo: {}/{}", cargo.units, cargo.capacity);
+    for item in &cargo.inventory {
+        println!(
+            "  {} x{}",
+            serde_json::to_string(&item.symbol).unwrap(),
+            item.units
+        );
+    }
+}
+
+fn display_cooldown(cooldown: &Cooldown) {
+    if cooldown.remaining_seconds > 0 {
+        println!(
+            "Cooldown: {}s of {}s remaining",
+            cooldown.remaining_seconds, cooldown.total_seconds
+        );
+    } else {
+        println!("Cooldown: None");
+    }
+}
+
+fn display_survey(survey: &Survey) {
+    let deposits: Vec<_> = survey
+        .deposits
+        .iter()
+        .map(|d| serde_json::to_string(&d.symbol).unwrap())
+        .collect();
+    println!(
+        "  {} [{}] at {} expires {}: {}",
+        survey.signature,
+        serde_json::to_string(&survey.size).unwrap(),
+        survey.symbol,
+        survey.expiration,
+        deposits.join(", ")
+    );
+}
+
+fn display_ship_short(ship: &ShipData) {
+    println!(
+        "{} | Role: {} | Status: {} | Location: {} | Fuel: {}/{} | Cargo: {}/{}",
+        ship.symbol,
+        serde_json::to_string(&ship.registration.role).unwrap(),
+        serde_json::to_string(&ship.nav.status).unwrap(),
+        ship.nav.waypoint_symbol,
+        ship.fuel.current,
+        ship.fuel.capacity,
+        ship.cargo.units,
+        ship.cargo.capacity
+    );
+}
+
+fn display_ship(ship: &ShipData) {
+    println!("");
+    println!("Ship: {}", ship.symbol);
+    println!("------------------------------");
+    println!(
+        "Role: {}",
+        serde_json::to_string(&ship.registration.role).unwrap()
+    );
+    println!(
+        "Nav: {} at {} ({})",
+        serde_json::to_string(&ship.nav.status).unwrap(),
+        ship.nav.waypoint_symbol,
+        serde_json::to_string(&ship.nav.flight_mode).unwrap()
+    );
+    println!(
+        "Frame: {} ({} module slots, {} mounting points, {} condition)",
+        ship.frame.name, ship.frame.module_slots, ship.frame.mounting_points, ship.frame.condition
+    );
+    println!(
+        "Reactor: {} ({} power output, {} condition)",
+        ship.reactor.name, ship.reactor.power_output, ship.reactor.condition
+    );
+    println!(
+        "Engine: {} ({} speed, {} condition)",
+        ship.engine.name, ship.engine.speed, ship.engine.condition
+    );
+
+    print!("Modules: ");
+    if !ship.modules.is_empty() {
+        let names: Vec<_> = ship.modules.iter().map(|m| m.name.as_str()).collect();
+        println!("{}", names.join(", "));
+    } else {
+        println!("None");
+    }
+
+    print!("Mounts: ");
+    if !ship.mounts.is_empty() {
+        let names: Vec<_> = ship.mounts.iter().map(|m| m.name.as_str()).collect();
+        println!("{}", names.join(", "));
+    } else {
+        println!("None");
+    }
+
+    println!(
+        "Crew: {} of {} required, {} capacity, {} morale",
+        ship.crew.current, ship.crew.required, ship.crew.capacity, ship.crew.morale
+    );
+    println!("Fuel: {}/{}", ship.fuel.current, ship.fuel.capacity);
+
+    display_cargo(&ship.cargo);
+    display_cooldown(&ship.cooldown);
+    println!();
+}
+
+impl ShipCommand {
+    pub async fn handle(&self, application: &mut Application) -> anyhow::Result<()> {
+        let output = application.output;
+        let limiter = application.limiter.clone();
+        let (ShipCommand::List { callsign, .. }
+        | ShipCommand::Info { callsign, .. }
+        | ShipCommand::Orbit { callsign, .. }
+        | ShipCommand::Dock { callsign, .. }
+        | ShipCommand::Navigate { callsign, .. }
+        | ShipCommand::WaitArrival { callsign, .. }
+        | ShipCommand::Survey { callsign, .. }
+        | ShipCommand::Extract { callsign, .. }
+        | ShipCommand::Siphon { callsign, .. }
+        | ShipCommand::Refine { callsign, .. }
+        | ShipCommand::Jettison { callsign, .. }
+        | ShipCommand::Buy { callsign, .. }
+        | ShipCommand::Sell { callsign, .. }
+        | ShipCommand::Transfer { callsign, .. }
+        | ShipCommand::FlightMode { callsign, .. }) = self;
+        application.load_agent(callsign).await;
+
+        match self {
+            ShipCommand::List { callsign } => {
+                let ships: Vec<_> = application
+                    .agent(callsign)?
+                    .ships()
+                    .map(|f| &f.1.data)
+                    .collect();
+                render(output, &ships, || {
+                    ships.iter().for_each(|ship| display_ship_short(ship))
+                })?;
+            }
+            ShipCommand::Info { callsign, ship } => {
+                let ship = find_ship(application, callsign, ship)?;
+                render(output, &ship.data, || display_ship(&ship.data))?;
+            }
+            ShipCommand::Orbit { callsign, ship } => {
+                let ship = find_ship(application, callsign, ship)?;
+                match limited!(limiter, ship.orbit()) {
+                    Ok(nav) => render(output, &nav, || display_nav(&nav))?,
+                    Err(e) => anyhow::bail!("Failed to orbit: {:?}", e),
+                }
+            }
+            ShipCommand::Dock { callsign, ship } => {
+                let ship = find_ship(application, callsign, ship)?;
+                match limited!(limiter, ship.dock()) {
+                    Ok(nav) => render(output, &nav, || display_nav(&nav))?,
+                    Err(e) => anyhow::bail!("Failed to dock: {:?}", e),
+                }
+            }
+            ShipCommand::Navigate { callsign, ship, to } => {
+                let client = application.agent_client(callsign);
+                let ship = find_ship(application, callsign, ship)?;
+                let Some(client) = client else {
+                    anyhow::bail!("No known agent with that callsign");
+                };
+
+                let destination = {
+                    let system = System::new(client, &ship.data.nav.system_symbol);
+                    match limited!(limiter, system.get_waypoint(to)) {
+                        Ok(waypoint) => waypoint.data,
+                        Err(e) => anyhow::bail!("Failed to look up destination: {}", e),
+                    }
+                };
+                let origin = &ship.data.nav.route.destination;
+                let distance = distance((origin.x, origin.y), (destination.x, destination.y));
+                let required = fuel_required(distance, &ship.data.nav.flight_mode);
+                if ship.data.fuel.capacity > 0 && required > ship.data.fuel.current {
+                    anyhow::bail!(
+                        "Not enough fuel to reach {}: need {}, have {}",
+                        to,
+                        required,
+                        ship.data.fuel.current
+                    );
+                }
+
+                if matches!(ship.data.nav.status, ShipNavStatus::Docked) {
+                    if let Err(e) = limited!(limiter, ship.orbit()) {
+                        anyhow::bail!("Ship is docked and could not move to orbit: {:?}", e);
+                    }
+                }
+                match limited!(limiter, ship.navigate(to)) {
+                    Ok(navigation) => render(output, &navigation, || {
+                        let route = &navigation.nav.route;
+                        println!(
+                            "Departed {} at {}",
+                            route.origin.symbol, route.departure_time
+                        );
+                        println!(
+                            "Arriving at {} at {}",
+                            route.destination.symbol, route.arrival
+                        );
+                        if let Some(consumed) = &navigation.fuel.consumed {
+                            println!(
+                                "Fuel consumed: {} ({}/{} remaining)",
+                                consumed.amount, navigation.fuel.current, navigation.fuel.capacity
+                            );
+                        }
+                        println!(
+                            "Route: {} -> {} ({})",
+                            route.origin.symbol,
+                            route.destination.symbol,
+                            serde_json::to_string(&navigation.nav.flight_mode).unwrap()
+                        );
+                    })?,
+                    Err(e) => anyhow::bail!("Failed to navigate: {:?}", e),
+                }
+            }
+            ShipCommand::WaitArrival { callsign, ship } => {
+                let ship = find_ship(application, callsign, ship)?;
+                if !matches!(ship.data.nav.status, ShipNavStatus::InTransit) {
+                    render(output, &ship.data.nav, || {
+                        println!("Ship is not in transit");
+                        display_nav(&ship.data.nav);
+                    })?;
+                    return Ok(());
+                }
+
+                let arrival = match DateTime::parse_from_rfc3339(&ship.data.nav.route.arrival) {
+                    Ok(arrival) => arrival.with_timezone(&Utc),
+                    Err(e) => anyhow::bail!("Could not read arrival time: {}", e),
+                };
+                let show_countdown = output == OutputFormat::Text;
+                if show_countdown {
+                    println!(
+                        "Waiting for {} to arrive at {}, Ctrl-C to stop waiting",
+                        ship.data.symbol, ship.data.nav.route.destination.symbol
+                    );
+                }
+
+                // Ctrl-C is handled by the REPL, which cancels whichever command is running
+                countdown(arrival, show_countdown).await;
+
+                if let Err(e) = limited!(limiter, ship.refresh()) {
+                    anyhow::bail!("Failed to refresh ship: {:?}", e);
+                }
+                render(output, &ship.data.nav, || {
+                    println!("Arrived at {}", ship.data.nav.waypoint_symbol);
+                    display_nav(&ship.data.nav);
+                })?;
+            }
+            ShipCommand::Survey { callsign, ship } => {
+                let ship = find_ship(application, callsign, ship)?;
+                let survey = match limited!(limiter, ship.survey()) {
+                    Ok(survey) => survey,
+                    Err(e) => anyhow::bail!("Failed to survey: {:?}", e),
+                };
+                render(output, &survey, || {
+                    println!("Surveys:");
+                    survey.surveys.iter().for_each(display_survey);
+                    display_cooldown(&survey.cooldown);
+                })?;
+                application.surveys.add(survey.surveys);
+                application.surveys.save()?;
+            }
+            ShipCommand::Extract {
+                callsign,
+                ship,
+                survey,
+            } => {
+                let survey = match survey {
+                    Some(signature) => match application.surveys.find(signature) {
+                        Some(survey) => Some(survey.clone()),
+                        None => anyhow::bail!("No stored survey with that signature"),
+                    },
+                    None => None,
+                };
+                let ship = find_ship(application, callsign, ship)?;
+                match limited!(limiter, ship.extract(survey.as_ref())) {
+                    Ok(extract) => render(output, &extract, || {
+                        println!(
+                            "Extracted {} {}",
+                            extract.extraction.extraction_yield.units,
+                            serde_json::to_string(&extract.extraction.extraction_yield.symbol)
+                                .unwrap()
+                        );
+                        display_cargo(&extract.cargo);
+                        display_cooldown(&extract.cooldown);
+                    })?,
+                    Err(e) => anyhow::bail!("Failed to extract: {:?}", e),
+                }
+            }
+            ShipCommand::Siphon { callsign, ship } => {
+                let ship = find_ship(application, callsign, ship)?;
+                match limited!(limiter, ship.siphon()) {
+                    Ok(siphon) => render(output, &siphon, || {
+                        println!(
+                            "Siphoned {} {}",
+                            siphon.siphon.siphon_yield.units,
+                            serde_json::to_string(&siphon.siphon.siphon_yield.symbol).unwrap()
+                        );
+                        display_cargo(&siphon.cargo);
+                        display_cooldown(&siphon.cooldown);
+                    })?,
+                    Err(e) => anyhow::bail!("Failed to siphon: {:?}", e),
+                }
+            }
+            ShipCommand::Refine {
+                callsign,
+                ship,
+                produce,
+            } => {
+                let ship = find_ship(application, callsign, ship)?;
+                match limited!(limiter, ship.refine(RefineProduce::from(produce.clone()))) {
+                    Ok(refine) => render(output, &refine, || {
+                        for consumed in &refine.consumed {
+                            println!(
+                                "Consumed {} {}",
+                                consumed.units,
+                                serde_json::to_string(&consumed.trade_symbol).unwrap()
+                            );
+                        }
+                        for produced in &refine.produced {
+                            println!(
+                                "Produced {} {}",
+                                produced.units,
+                                serde_json::to_string(&produced.trade_symbol).unwrap()
+                            );
+                        }
+                        display_cargo(&refine.cargo);
+                        display_cooldown(&refine.cooldown);
+                    })?,
+                    Err(e) => anyhow::bail!("Failed to refine: {:?}", e),
+                }
+            }
+            ShipCommand::Jettison {
+                callsign,
+                ship,
+                good,
+                units,
+            } => {
+                let ship = find_ship(application, callsign, ship)?;
+                match limited!(limiter, ship.jettison(good.clone(), *units)) {
+                    Ok(cargo) => render(output, &cargo, || {
+                        println!(
+                            "Jettisoned {} {}",
+                            units,
+                            serde_json::to_string(good).unwrap()
+                        );
+                        display_cargo(&cargo);
+                    })?,
+                    Err(e) => anyhow::bail!("Failed to jettison: {:?}", e),
+                }
+            }
+            ShipCommand::Buy {
+                callsign,
+                ship,
+                good,
+                units,
+            } => {
+                let ship = find_ship(application, callsign, ship)?;
+                let trade = match limited!(limiter, ship.purchase_cargo(good.clone(), *units)) {
+                    Ok(trade) => trade,
+                    Err(e) => anyhow::bail!("Failed to buy cargo: {:?}", e),
+                };
+                render(output, &trade, || {
+                    display_transaction("Bought", &trade.transaction);
+                    display_cargo(&trade.cargo);
+                    println!("Credits: {}", trade.agent.credits);
+                })?;
+                update_agent(application, callsign, trade.agent);
+            }
+            ShipCommand::Sell {
+                callsign,
+                ship,
+                good,
+                units,
+                all,
+            } => {
+                let client = application.agent_client(callsign);
+                let ship = find_ship(application, callsign, ship)?;
+                let Some(client) = client else {
+                    anyhow::bail!("No known agent with that callsign");
+                };
+
+                let held = ship
+                    .data
+                    .cargo
+                    .inventory
+                    .iter()
+                    .find(|item| &item.symbol == good)
+                    .map_or(0, |item| item.units);
+                if held == 0 {
+                    anyhow::bail!("Ship holds none of that good");
+                }
+                // Clap requires --units unless --all is given
+                let units = if *all { held } else { units.unwrap_or(held) };
+                if units > held {
+                    anyhow::bail!("Ship only holds {} of that good", held);
+                }
+
+                let market = {
+                    let system = System::new(client, &ship.data.nav.system_symbol);
+                    match limited!(limiter, system.get_market(&ship.data.nav.waypoint_symbol)) {
+                        Ok(market) => market,
+                        Err(e) => anyhow::bail!("Failed to look up market: {}", e),
+                    }
+                };
+                let Some(trade_volume) = market
+                    .trade_goods
+                    .iter()
+                    .flatten()
+                    .find(|trade_good| &trade_good.symbol == good)
+                    .map(|trade_good| trade_good.trade_volume)
+                else {
+                    anyhow::bail!("The market here does not buy that good");
+                };
+
+                // Markets reject transactions larger than their trade volume, so sell in batches
+                let mut remaining = units;
+                let mut transactions = Vec::new();
+                let mut latest_agent = None;
+                let mut failure = None;
+                while remaining > 0 {
+                    let batch = remaining.min(trade_volume.max(1));
+                    match limited!(limiter, ship.sell_cargo(good.clone(), batch)) {
+                        Ok(trade) => {
+                            remaining -= batch;
+                            transactions.push(trade.transaction);
+                            latest_agent = Some(trade.agent);
+                            ship.data.cargo = trade.cargo;
+                        }
+                        Err(e) => {
+                            failure = Some(format!("Failed to sell cargo: {:?}", e));
+                            break;
+                        }
+                    }
+                }
+                // Batches sold before a failure are still reported and recorded
+                if !transactions.is_empty() {
+                    let sale = json!({
+                        "transactions": transactions,
+                        "cargo": ship.data.cargo,
+                        "agent": latest_agent,
+                    });
+                    render(output, &sale, || {
+                        for transaction in &transactions {
+                            display_transaction("Sold", transaction);
+                        }
+                        display_cargo(&ship.data.cargo);
+                        if let Some(data) = &latest_agent {
+                            println!("Credits: {}", data.credits);
+                        }
+                    })?;
+                }
+                application.markets.update(&market)?;
+                if let Some(data) = latest_agent {
+                    update_agent(application, callsign, data);
+                }
+                if let Some(failure) = failure {
+                    anyhow::bail!(failure);
+                }
+            }
+            ShipCommand::Transfer {
+                callsign,
+                ship,
+                to_ship,
+                good,
+                units,
+            } => {
+                let ship = find_ship(application, callsign, ship)?;
+                let transfer =
+                    match limited!(limiter, ship.transfer_cargo(good.clone(), *units, to_ship)) {
+                        Ok(transfer) => transfer,
+                        Err(e) => anyhow::bail!("Failed to transfer cargo: {:?}", e),
+                    };
+                render(output, &transfer, || {
+                    println!(
+                        "Transferred {} {} to {}",
+                        units,
+                        serde_json::to_string(good).unwrap(),
+                        to_ship
+                    );
+                    display_cargo(&transfer.cargo);
+                })?;
+                // The receiving ship may belong to another agent or not be loaded
+                if let Some(agent) = application.agents.get_mut(callsign) {
+                    if agent.ships().any(|(symbol, _)| symbol == to_ship) {
+                        agent.edit_ship(to_ship).data.cargo = transfer.target_cargo;
+                    }
+                }
+            }
+            ShipCommand::FlightMode {
+                callsign,
+                ship,
+                mode,
+            } => {
+                let ship = find_ship(application, callsign, ship)?;
+                match limited!(
+                    limiter,
+                    ship.set_flight_mode(ShipFlightMode::from(mode.clone()))
+                ) {
+                    Ok(nav) => render(output, &nav, || display_nav(&nav))?,
+                    Err(e) => anyhow::bail!("Failed to set flight mode: {:?}", e),
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(ValueEnum, Clone, Debug)]
+pub enum FlightModeArg {
+    Cruise,
+    Burn,
+    Drift,
+    Stealth,
+}
+
+impl From<FlightModeArg> for ShipFlightMode {
+    fn from(arg: FlightModeArg) -> Self {
+        match arg {
+            FlightModeArg::Cruise => ShipFlightMode::Cruise,
+            FlightModeArg::Burn => ShipFlightMode::Burn,
+            FlightModeArg::Drift => ShipFlightMode::Drift,
+            FlightModeArg::Stealth => ShipFlightMode::Stealth,
+        }
+    }
+}
+
+#[derive(ValueEnum, Clone, Debug)]
+pub enum RefineProduceArg {
+    Iron,
+    Copper,
+    Silver,
+    Gold,
+    Aluminum,
+    Platinum,
+    Uranite,
+    Meritium,
+    Fuel,
+}
+
+impl From<RefineProduceArg> for RefineProduce {
+    fn from(arg: RefineProduceArg) -> Self {
+        match arg {
+            RefineProduceArg::Iron => RefineProduce::Iron,
+            RefineProduceArg::Copper => RefineProduce::Copper,
+            RefineProduceArg::Silver => RefineProduce::Silver,
+            RefineProduceArg::Gold => RefineProduce::Gold,
+            RefineProduceArg::Aluminum => RefineProduce::Aluminum,
+            RefineProduceArg::Platinum => RefineProduce::Platinum,
+            RefineProduceArg::Uranite => RefineProduce::Uranite,
+            RefineProduceArg::Meritium => RefineProduce::Meritium,
+            RefineProduceArg::Fuel => RefineProduce::Fuel,
+        }
+    }
+}
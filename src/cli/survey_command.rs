@@ -0,0 +1,107 @@
+use chrono::Utc;
+use clap::Subcommand;
+use serde_json::json;
+use space_traders_sdk::{market::TradeSymbol, survey::Survey};
+
+use crate::{
+    output::render,
+    survey_store::{deposit_share, is_expired},
+    utils::parse_trade_symbol,
+    Application,
+};
+
+#[derive(Subcommand, Debug)]
+pub enum SurveyCommand {
+    /// List stored surveys
+    List {
+        /// Only show surveys taken at this waypoint
+        #[arg(short, long)]
+        waypoint: Option<String>,
+    },
+    /// Remove expired surveys from the store
+    Prune,
+    /// Find the stored survey with the best yield of a good at a waypoint
+    Best {
+        /// Waypoint Symbol
+        #[arg(short, long)]
+        waypoint: String,
+        /// Trade symbol of the good to target
+        #[arg(short, long, value_parser = parse_trade_symbol)]
+        good: TradeSymbol,
+    },
+}
+
+fn display_survey_short(survey: &Survey) {
+    let deposits: Vec<_> = survey
+        .deposits
+        .iter()
+        .map(|d| serde_json::to_string(&d.symbol).unwrap())
+        .collect();
+    println!(
+        "{} | Size: {} | Expires: {}{} | Deposits: {}",
+        survey.signature,
+        serde_json::to_string(&survey.size).unwrap(),
+        survey.expiration,
+        if is_expired(survey, Utc::now()) {
+            " (expired)"
+        } else {
+            ""
+        },
+        deposits.join(", ")
+    );
+}
+
+impl SurveyCommand {
+    pub async fn handle(&self, application: &mut Application) -> anyhow::Result<()> {
+        match self {
+            SurveyCommand::List { waypoint } => {
+                let mut waypoints: Vec<_> = application
+                    .surveys
+                    .surveys
+                    .iter()
+                    .filter(|(symbol, _)| waypoint.as_ref().is_none_or(|w| w == *symbol))
+                    .collect();
+                waypoints.sort_by(|a, b| a.0.cmp(b.0));
+                let surveys: Vec<_> = waypoints
+                    .iter()
+                    .flat_map(|(_, surveys)| surveys.iter())
+                    .collect();
+                render(application.output, &surveys, || {
+                    if waypoints.is_empty() {
+                        println!("No stored surveys");
+                    }
+                    for (symbol, surveys) in &waypoints {
+                        println!("");
+                        println!("Surveys at {}", symbol);
+                        println!("------------------------------");
+                        surveys.iter().for_each(display_survey_short);
+                    }
+                })?;
+            }
+            SurveyCommand::Prune => {
+                let removed = application.surveys.prune(Utc::now());
+                application.surveys.save()?;
+                render(application.output, &json!({ "removed": removed }), || {
+                    println!("Removed {} expired surveys", removed)
+                })?;
+            }
+            SurveyCommand::Best { waypoint, good } => {
+                match application.surveys.best(waypoint, good, Utc::now()) {
+                    Some((survey, _)) => render(application.output, survey, || {
+                        display_survey_short(survey);
+                        println!(
+                            "Expected share of {}: {:.0}%",
+                            serde_json::to_string(good).unwrap(),
+                            deposit_share(survey, good) * 100.0
+                        );
+                    })?,
+                    None => {
+                        anyhow::bail!("No unexpired survey at that waypoint contains that good")
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -1,5 +1,10 @@
+use std::sync::Arc;
+
 use clap::Subcommand;
 use space_traders_sdk::{
+    market::MarketData,
+    ship::ShipType,
+    shipyard::{ShipyardData, ShipyardShip},
     space_traders_client::SpaceTradersClient,
     system::{
         waypoint::{WaypointData, WaypointTraitSymbol, WaypointType},
@@ -7,7 +12,13 @@ use space_traders_sdk::{
     },
 };
 
-use crate::Application;
+use crate::{
+    output::render,
+    rate_limiter::limited,
+    table::{Table, TableArgs},
+    utils::enum_name,
+    Application,
+};
 use clap::ValueEnum;
 
 #[derive(Subcommand, Debug)]
@@ -23,6 +34,8 @@ pub enum SystemCommand {
         /// Trait of waypoint to search for
         #[arg(long)]
         r#trait: Option<WaypointTraitSymbolArg>,
+        #[command(flatten)]
+        table: TableArgs,
     },
     /// Waypoint level commands
     Waypoint {
@@ -40,9 +53,32 @@ pub enum SystemCommand {
 #[derive(Subcommand, Debug)]
 pub enum WaypointCommand {
     /// Get shipyard info
-    Shipyard,
+    Shipyard {
+        /// Callsign of an agent to view the shipyard as, needed to see full listings
+        #[arg(short, long)]
+        callsign: Option<String>,
+        #[command(subcommand)]
+        command: Option<ShipyardCommand>,
+    },
     /// Get market info
-    Market,
+    Market {
+        /// Callsign of an agent to view the market as, needed to see live prices
+        #[arg(short, long)]
+        callsign: Option<String>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ShipyardCommand {
+    /// Purchase a ship at this shipyard
+    Buy {
+        /// Callsign of the agent
+        #[arg(short, long)]
+        callsign: String,
+        /// Type of ship to purchase
+        #[arg(short = 't', long)]
+        ship_type: ShipTypeArg,
+    },
 }
 
 fn _display_waypoint(waypoint: &WaypointData) {
@@ -97,71 +133,257 @@ fn _display_waypoint(waypoint: &WaypointData) {
     println!();
 }
 
-fn display_waypoint_short(waypoint: &WaypointData) {
-    let trait_names: Vec<_> = waypoint
-        .traits
-        .iter()
-        .map(|t| serde_json::to_string(&t.symbol).unwrap())
-        .collect();
-    println!(
-        "{} [{}] with traits {}",
-        waypoint.symbol,
-        serde_json::to_string(&waypoint.waypoint_type).unwrap(),
-        if trait_names.is_empty() {
-            String::from("No traits")
+fn waypoints_table(waypoints: &[&WaypointData]) -> Table {
+    let mut table = Table::new(&["symbol", "type", "x", "y", "traits"]);
+    for waypoint in waypoints {
+        let trait_names: Vec<_> = waypoint
+            .traits
+            .iter()
+            .map(|t| enum_name(&t.symbol))
+            .collect();
+        table.row(vec![
+            waypoint.symbol.clone(),
+            enum_name(&waypoint.waypoint_type),
+            waypoint.x.to_string(),
+            waypoint.y.to_string(),
+            trait_names.join(", "),
+        ]);
+    }
+    table
+}
+
+/// Client to view waypoint details with, authenticated as the agent when one is given
+fn view_client(
+    application: &Application,
+    callsign: &Option<String>,
+) -> Option<Arc<SpaceTradersClient>> {
+    match callsign {
+        Some(callsign) => application.agent_client(callsign),
+        None => Some(application.public_client()),
+    }
+}
+
+fn display_market(market: &MarketData) {
+    println!("");
+    println!("Market: {}", market.symbol);
+    println!("------------------------------");
+    for (label, goods) in [
+        ("Imports", &market.imports),
+        ("Exports", &market.exports),
+        ("Exchange", &market.exchange),
+    ] {
+        print!("{}: ", label);
+        if !goods.is_empty() {
+            let names: Vec<_> = goods
+                .iter()
+                .map(|g| serde_json::to_string(&g.symbol).unwrap())
+                .collect();
+            println!("{}", names.join(", "));
         } else {
-            trait_names.join(", ")
+            println!("None");
+        }
+    }
+
+    println!("Trade Goods:");
+    match &market.trade_goods {
+        Some(trade_goods) if !trade_goods.is_empty() => {
+            for good in trade_goods {
+                println!(
+                    "  {} [{}] buy {} / sell {} | supply {} | activity {} | volume {}",
+                    serde_json::to_string(&good.symbol).unwrap(),
+                    serde_json::to_string(&good.trade_good_type).unwrap(),
+                    good.purchase_price,
+                    good.sell_price,
+                    serde_json::to_string(&good.supply).unwrap(),
+                    good.activity
+                        .as_ref()
+                        .map(|a| serde_json::to_string(a).unwrap())
+                        .unwrap_or_else(|| String::from("Unknown")),
+                    good.trade_volume
+                );
+            }
         }
+        _ => println!("  Not visible, an agent with a ship at this waypoint is required"),
+    }
+    println!();
+}
+
+fn display_shipyard(shipyard: &ShipyardData) {
+    println!("");
+    println!("Shipyard: {}", shipyard.symbol);
+    println!("------------------------------");
+    print!("Ship Types: ");
+    if !shipyard.ship_types.is_empty() {
+        let types: Vec<_> = shipyard
+            .ship_types
+            .iter()
+            .map(|t| serde_json::to_string(&t.ship_type).unwrap())
+            .collect();
+        println!("{}", types.join(", "));
+    } else {
+        println!("None");
+    }
+    println!("Modification Fee: {}", shipyard.modifications_fee);
+
+    println!("Listings:");
+    match &shipyard.ships {
+        Some(ships) if !ships.is_empty() => ships.iter().for_each(display_shipyard_ship),
+        _ => println!("  Not visible, an agent with a ship at this waypoint is required"),
+    }
+    println!();
+}
+
+fn display_shipyard_ship(ship: &ShipyardShip) {
+    println!(
+        "  {} ({}) - {} credits | supply {}",
+        ship.name,
+        serde_json::to_string(&ship.ship_type).unwrap(),
+        ship.purchase_price,
+        serde_json::to_string(&ship.supply).unwrap()
+    );
+    println!(
+        "    Frame: {} ({} module slots, {} mounting points, {} fuel capacity)",
+        ship.frame.name,
+        ship.frame.module_slots,
+        ship.frame.mounting_points,
+        ship.frame.fuel_capacity
+    );
+    println!(
+        "    Reactor: {} ({} power output)",
+        ship.reactor.name, ship.reactor.power_output
+    );
+    println!(
+        "    Engine: {} ({} speed)",
+        ship.engine.name, ship.engine.speed
+    );
+
+    print!("    Modules: ");
+    if !ship.modules.is_empty() {
+        let names: Vec<_> = ship.modules.iter().map(|m| m.name.as_str()).collect();
+        println!("{}", names.join(", "));
+    } else {
+        println!("None");
+    }
+
+    print!("    Mounts: ");
+    if !ship.mounts.is_empty() {
+        let names: Vec<_> = ship.mounts.iter().map(|m| m.name.as_str()).collect();
+        println!("{}", names.join(", "));
+    } else {
+        println!("None");
+    }
+
+    println!(
+        "    Crew: {} required, {} capacity",
+        ship.crew.required, ship.crew.capacity
     );
 }
 
 impl SystemCommand {
-    pub async fn handle(&self, _application: &mut Application) -> anyhow::Result<()> {
+    pub async fn handle(&self, application: &mut Application) -> anyhow::Result<()> {
         match self {
             SystemCommand::ListWaypoints {
                 system,
                 r#type,
                 r#trait,
+                table,
             } => {
+                table.check(application.output)?;
                 let type_converted = r#type.as_ref().map(|t| WaypointType::from(t.clone()));
                 let trait_converted = r#trait
                     .as_ref()
                     .map(|t| WaypointTraitSymbol::from(t.clone()));
-                match System::new(SpaceTradersClient::new(None).into(), system)
-                    .list_waypoints(type_converted.clone(), trait_converted.clone())
-                    .await
-                {
+                let client = System::new(application.public_client(), system);
+                match limited!(
+                    application.limiter,
+                    client.list_waypoints(type_converted.clone(), trait_converted.clone())
+                ) {
                     Ok(waypoints) => {
-                        println!("");
-                        print!("Waypoints in system {}", system);
-                        if let Some(waypoint_type) = type_converted {
-                            print!(
-                                ", with type {}",
-                                serde_json::to_string(&waypoint_type).unwrap()
-                            );
-                        }
-                        if let Some(waypoint_trait) = trait_converted {
-                            print!(
-                                ", with trait {}",
-                                serde_json::to_string(&waypoint_trait).unwrap()
-                            );
-                        }
-                        println!("");
-                        println!("------------------------------");
-                        waypoints
-                            .iter()
-                            .for_each(|waypoint| display_waypoint_short(&waypoint.data))
+                        let waypoints: Vec<_> = waypoints.iter().map(|w| &w.data).collect();
+                        application
+                            .systems
+                            .entry(system.clone())
+                            .or_default()
+                            .extend(waypoints.iter().map(|w| w.symbol.clone()));
+                        let text = waypoints_table(&waypoints).format(table)?;
+                        render(application.output, &waypoints, || {
+                            println!("");
+                            print!("Waypoints in system {}", system);
+                            if let Some(waypoint_type) = &type_converted {
+                                print!(", with type {}", enum_name(waypoint_type));
+                            }
+                            if let Some(waypoint_trait) = &trait_converted {
+                                print!(", with trait {}", enum_name(waypoint_trait));
+                            }
+                            println!();
+                            print!("{}", text);
+                        })?;
                     }
-                    Err(e) => eprintln!("Error listing waypoints: {}", e),
+                    Err(e) => anyhow::bail!("Error listing waypoints: {}", e),
                 }
             }
             SystemCommand::Waypoint {
-                system: _,
-                waypoint: _,
+                system,
+                waypoint,
                 command,
             } => match command {
-                WaypointCommand::Shipyard => todo!(),
-                WaypointCommand::Market => todo!(),
+                WaypointCommand::Shipyard {
+                    command:
+                        Some(ShipyardCommand::Buy {
+                            callsign,
+                            ship_type,
+                        }),
+                    ..
+                } => {
+                    application.load_agent(callsign).await;
+                    let output = application.output;
+                    let limiter = application.limiter.clone();
+                    let agent = application.agent_mut(callsign)?;
+                    let purchase = match limited!(
+                        limiter,
+                        agent.purchase_ship(ShipType::from(ship_type), waypoint)
+                    ) {
+                        Ok(purchase) => purchase,
+                        Err(e) => anyhow::bail!("Failed to purchase ship: {:?}", e),
+                    };
+                    render(output, &purchase, || {
+                        println!(
+                            "Purchased {} for {} credits",
+                            purchase.ship.symbol, purchase.transaction.price
+                        );
+                        println!("Credits remaining: {}", purchase.agent.credits);
+                    })?;
+                    agent.data = purchase.agent;
+                    agent.add_ship(purchase.ship);
+                }
+                WaypointCommand::Shipyard {
+                    callsign,
+                    command: None,
+                } => {
+                    let Some(client) = view_client(application, callsign) else {
+                        anyhow::bail!("No known agent with that callsign");
+                    };
+                    let client = System::new(client, system);
+                    match limited!(application.limiter, client.get_shipyard(waypoint)) {
+                        Ok(shipyard) => render(application.output, &shipyard, || {
+                            display_shipyard(&shipyard)
+                        })?,
+                        Err(e) => anyhow::bail!("Error getting shipyard: {}", e),
+                    }
+                }
+                WaypointCommand::Market { callsign } => {
+                    let Some(client) = view_client(application, callsign) else {
+                        anyhow::bail!("No known agent with that callsign");
+                    };
+                    let client = System::new(client, system);
+                    match limited!(application.limiter, client.get_market(waypoint)) {
+                        Ok(market) => {
+                            application.markets.update(&market)?;
+                            render(application.output, &market, || display_market(&market))?;
+                        }
+                        Err(e) => anyhow::bail!("Error getting market: {}", e),
+                    }
+                }
             },
         }
 
@@ -208,6 +430,43 @@ impl From<WaypointTypeArg> for WaypointType {
     }
 }
 
+#[derive(ValueEnum, Clone, Debug)]
+pub enum ShipTypeArg {
+    Probe,
+    MiningDrone,
+    SiphonDrone,
+    Interceptor,
+    LightHauler,
+    CommandFrigate,
+    Explorer,
+    HeavyFreighter,
+    LightShuttle,
+    OreHound,
+    RefiningFreighter,
+    Surveyor,
+    BulkFreighter,
+}
+
+impl From<&ShipTypeArg> for ShipType {
+    fn from(arg: &ShipTypeArg) -> Self {
+        match arg {
+            ShipTypeArg::Probe => ShipType::Probe,
+            ShipTypeArg::MiningDrone => ShipType::MiningDrone,
+            ShipTypeArg::SiphonDrone => ShipType::SiphonDrone,
+            ShipTypeArg::Interceptor => ShipType::Interceptor,
+            ShipTypeArg::LightHauler => ShipType::LightHauler,
+            ShipTypeArg::CommandFrigate => ShipType::CommandFrigate,
+            ShipTypeArg::Explorer => ShipType::Explorer,
+            ShipTypeArg::HeavyFreighter => ShipType::HeavyFreighter,
+            ShipTypeArg::LightShuttle => ShipType::LightShuttle,
+            ShipTypeArg::OreHound => ShipType::OreHound,
+            ShipTypeArg::RefiningFreighter => ShipType::RefiningFreighter,
+            ShipTypeArg::Surveyor => ShipType::Surveyor,
+            ShipTypeArg::BulkFreighter => ShipType::BulkFreighter,
+        }
+    }
+}
+
 #[derive(ValueEnum, Clone, Debug)]
 pub enum WaypointTraitSymbolArg {
     Uncharted,
@@ -0,0 +1,400 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use clap::{Arg, Command, CommandFactory};
+
+use crate::Application;
+
+/// Words the REPL understands on top of the clap commands
+pub const REPL_COMMANDS: &[&str] = &["alias", "exit", "help", "source", "unalias", "unuse", "use"];
+
+/// The REPL command tree with the `help` subcommands and `--help` flags clap adds when
+/// parsing, so completion and highlighting know them too
+pub fn repl_command() -> Command {
+    let mut command = crate::cli::ReplCli::command();
+    command.build();
+    command
+}
+
+/// Live values offered when completing arguments, refreshed after every command
+#[derive(Debug, Default, Clone)]
+pub struct CompletionValues {
+    pub callsigns: BTreeSet<String>,
+    /// Contract IDs keyed by the callsign of the agent holding them
+    pub contracts: BTreeMap<String, BTreeSet<String>>,
+    /// Ship symbols keyed by the callsign of the agent owning them
+    pub ships: BTreeMap<String, BTreeSet<String>>,
+    pub systems: BTreeSet<String>,
+    pub waypoints: BTreeSet<String>,
+    pub surveys: BTreeSet<String>,
+    pub aliases: BTreeSet<String>,
+}
+
+impl CompletionValues {
+    pub fn collect(application: &Application) -> Self {
+        let mut values = CompletionValues::default();
+        values.callsigns.extend(
+            application
+                .config
+                .agents
+                .iter()
+                .map(|agent| agent.id.clone()),
+        );
+
+        for (callsign, agent) in &application.agents {
+            values.callsigns.insert(callsign.clone());
+            values.contracts.insert(
+                callsign.clone(),
+                agent.contracts().map(|(id, _)| id.clone()).collect(),
+            );
+            values.ships.insert(
+                callsign.clone(),
+                agent.ships().map(|(symbol, _)| symbol.clone()).collect(),
+            );
+            for (_, ship) in agent.ships() {
+                values.systems.insert(ship.data.nav.system_symbol.clone());
+                values
+                    .waypoints
+                    .insert(ship.data.nav.waypoint_symbol.clone());
+            }
+        }
+
+        for (system, waypoints) in &application.systems {
+            values.systems.insert(system.clone());
+            values.waypoints.extend(waypoints.iter().cloned());
+        }
+        values
+            .waypoints
+            .extend(application.markets.markets.keys().cloned());
+        values
+            .aliases
+            .extend(application.config.aliases.keys().cloned());
+        values.surveys.extend(
+            application
+                .surveys
+                .surveys
+                .values()
+                .flatten()
+                .map(|survey| survey.signature.clone()),
+        );
+        values
+    }
+
+    /// Values for an argument, contracts and ships are narrowed to the callsign given on
+    /// the line when there is one
+    fn for_arg(&self, arg: &Arg, given: &HashMap<String, String>) -> Vec<String> {
+        let owned = |by_callsign: &BTreeMap<String, BTreeSet<String>>| -> Vec<String> {
+            match given.get("callsign").and_then(|c| by_callsign.get(c)) {
+                Some(values) => values.iter().cloned().collect(),
+                None => by_callsign.values().flatten().cloned().collect(),
+            }
+        };
+
+        match arg.get_id().as_str() {
+            "callsign" => self.callsigns.iter().cloned().collect(),
+            "id" => owned(&self.contracts),
+            "ship" | "to_ship" => owned(&self.ships),
+            "waypoint" | "to" => self.waypoints.iter().cloned().collect(),
+            "system" => self.systems.iter().cloned().collect(),
+            "survey" => self.surveys.iter().cloned().collect(),
+            _ => arg
+                .get_possible_values()
+                .iter()
+                .filter(|value| !value.is_hide_set())
+                .map(|value| value.get_name().to_string())
+                .collect(),
+        }
+    }
+}
+
+/// How a word on the line was understood, used to pick its highlight color
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TokenKind {
+    Command,
+    Flag,
+    Value,
+    Unknown,
+}
+
+/// Follows a line word by word through the clap command tree
+struct Walker<'a> {
+    root: &'a Command,
+    aliases: &'a BTreeSet<String>,
+    command: &'a Command,
+    /// Flag whose value is expected next
+    pending: Option<&'a Arg>,
+    positionals: usize,
+    /// Arguments given so far keyed by id, flags without values map to an empty string
+    given: HashMap<String, String>,
+    /// REPL only command such as `use` or an alias, whose arguments clap does not know
+    repl_command: Option<String>,
+}
+
+impl<'a> Walker<'a> {
+    fn new(root: &'a Command, aliases: &'a BTreeSet<String>) -> Self {
+        Walker {
+            root,
+            aliases,
+            command: root,
+            pending: None,
+            positionals: 0,
+            given: HashMap::new(),
+            repl_command: None,
+        }
+    }
+
+    fn step(&mut self, word: &str) -> TokenKind {
+        if let Some(arg) = self.pending.take() {
+            self.given
+                .insert(arg.get_id().to_string(), word.to_string());
+            return TokenKind::Value;
+        }
+        if self.repl_command.is_some() {
+            return TokenKind::Value;
+        }
+
+        if word.len() > 1 && word.starts_with('-') {
+            let (flag, inline) = match word.split_once('=') {
+                Some((flag, value)) => (flag, Some(value)),
+                None => (word, None),
+            };
+            let Some(arg) = find_arg(self.root, self.command, flag) else {
+                return TokenKind::Unknown;
+            };
+            match inline {
+                Some(value) => {
+                    self.given
+                        .insert(arg.get_id().to_string(), value.to_string());
+                }
+                None if arg.get_action().takes_values() => self.pending = Some(arg),
+                None => {
+                    self.given.insert(arg.get_id().to_string(), String::new());
+                }
+            }
+            TokenKind::Flag
+        } else if let Some(sub) = self.command.find_subcommand(word) {
+            self.command = sub;
+            self.positionals = 0;
+            TokenKind::Command
+        } else if self.at_root() && (REPL_COMMANDS.contains(&word) || self.aliases.contains(word)) {
+            self.repl_command = Some(word.to_string());
+            TokenKind::Command
+        } else if let Some(arg) = self.command.get_positionals().nth(self.positionals) {
+            self.given
+                .insert(arg.get_id().to_string(), word.to_string());
+            self.positionals += 1;
+            TokenKind::Value
+        } else {
+            TokenKind::Unknown
+        }
+    }
+
+    fn at_root(&self) -> bool {
+        std::ptr::eq(self.command, self.root)
+    }
+}
+
+/// Splits a line into the finished words and the word under the cursor, along with
+/// where that last word starts
+fn split_line(line: &str) -> (Vec<String>, String, usize) {
+    let mut words = shell_words::split(line)
+        .unwrap_or_else(|_| line.split_whitespace().map(String::from).collect());
+    if line.is_empty() || line.ends_with(char::is_whitespace) {
+        return (words, String::new(), line.len());
+    }
+    let partial = words.pop().unwrap_or_default();
+    let start = line.rfind(char::is_whitespace).map_or(0, |i| i + 1);
+    (words, partial, start)
+}
+
+/// Works out what the last word of the line is filling in, a subcommand, a flag, the
+/// value of a flag or a positional argument, and returns where that word starts along
+/// with the candidates for it
+pub fn complete(root: &Command, values: &CompletionValues, line: &str) -> (usize, Vec<String>) {
+    let (done, partial, start) = split_line(line);
+    let mut walker = Walker::new(root, &values.aliases);
+    for word in &done {
+        walker.step(word);
+    }
+
+    let mut candidates = match (walker.pending, done.as_slice()) {
+        (_, [first]) if first == "use" => {
+            let mut names: Vec<String> = values.callsigns.iter().cloned().collect();
+            names.push(String::from("ship"));
+            names
+        }
+        (_, [first, kind]) if first == "use" && kind == "ship" => {
+            values.ships.values().flatten().cloned().collect()
+        }
+        (_, [first, ..]) if first == "unalias" => values.aliases.iter().cloned().collect(),
+        (Some(arg), _) => values.for_arg(arg, &walker.given),
+        (None, _) if walker.repl_command.is_some() => Vec::new(),
+        (None, _) if partial.starts_with('-') => walker
+            .command
+            .get_arguments()
+            .chain(root.get_arguments().filter(|arg| arg.is_global_set()))
+            .filter(|arg| !arg.is_hide_set())
+            .filter_map(|arg| arg.get_long().map(|long| format!("--{}", long)))
+            .collect(),
+        (None, _) => {
+            let mut names: Vec<String> = walker
+                .command
+                .get_subcommands()
+                .filter(|sub| !sub.is_hide_set())
+                .map(|sub| sub.get_name().to_string())
+                .collect();
+            if walker.at_root() {
+                names.extend(REPL_COMMANDS.iter().map(|name| name.to_string()));
+                names.extend(values.aliases.iter().cloned());
+            }
+            if let Some(arg) = walker.command.get_positionals().nth(walker.positionals) {
+                names.extend(values.for_arg(arg, &walker.given));
+            }
+            names
+        }
+    };
+    candidates.retain(|candidate| candidate.starts_with(&partial));
+    candidates.sort();
+    candidates.dedup();
+    (start, candidates)
+}
+
+/// Text to show greyed out after the cursor: the rest of the word when only one
+/// completion fits, otherwise what the command still needs. Arguments in `provided`
+/// are filled in by the session and are not asked for.
+pub fn hint(
+    root: &Command,
+    values: &CompletionValues,
+    line: &str,
+    provided: &[&str],
+) -> Option<String> {
+    if line.trim().is_empty() {
+        return None;
+    }
+    let (done, partial, _) = split_line(line);
+    if !partial.is_empty() {
+        let (_, candidates) = complete(root, values, line);
+        return match candidates.as_slice() {
+            [only] => only
+                .strip_prefix(partial.as_str())
+                .filter(|rest| !rest.is_empty())
+                .map(String::from),
+            _ => None,
+        };
+    }
+
+    let mut walker = Walker::new(root, &values.aliases);
+    for word in &done {
+        walker.step(word);
+    }
+    if walker.repl_command.is_some() {
+        return None;
+    }
+    if let Some(arg) = walker.pending {
+        return Some(value_name(arg));
+    }
+    if walker.command.has_subcommands() {
+        return Some(String::from("<command>"));
+    }
+
+    let missing: Vec<String> = walker
+        .command
+        .get_arguments()
+        .filter(|arg| arg.is_required_set())
+        .filter(|arg| {
+            let id = arg.get_id().as_str();
+            !walker.given.contains_key(id) && !provided.contains(&id)
+        })
+        .map(|arg| match arg.get_long() {
+            Some(long) => format!("--{} {}", long, value_name(arg)),
+            None => value_name(arg),
+        })
+        .collect();
+    if missing.is_empty() {
+        None
+    } else {
+        Some(missing.join(" "))
+    }
+}
+
+/// Colors each word of the line by what it is: commands, flags, values, or red for
+/// anything the command tree does not know
+pub fn highlight(root: &Command, values: &CompletionValues, line: &str) -> String {
+    let mut walker = Walker::new(root, &values.aliases);
+    let mut out = String::with_capacity(line.len() * 2);
+    let mut last = 0;
+    for (start, end) in word_spans(line) {
+        out.push_str(&line[last..start]);
+        let word = &line[start..end];
+        let unquoted = shell_words::split(word)
+            .ok()
+            .and_then(|mut words| words.pop())
+            .unwrap_or_else(|| word.to_string());
+        let color = match walker.step(&unquoted) {
+            TokenKind::Command => Some("1;32"),
+            TokenKind::Flag => Some("36"),
+            TokenKind::Value => None,
+            TokenKind::Unknown => Some("31"),
+        };
+        match color {
+            Some(color) => out.push_str(&format!("\x1b[{}m{}\x1b[0m", color, word)),
+            None => out.push_str(word),
+        }
+        last = end;
+    }
+    out.push_str(&line[last..]);
+    out
+}
+
+/// Byte ranges of the whitespace separated words in a line, keeping quoted text together
+fn word_spans(line: &str) -> Vec<(usize, usize)> {
+    let mut spans = Vec::new();
+    let mut start = None;
+    let mut quote = None;
+    for (i, c) in line.char_indices() {
+        match (start, quote) {
+            (_, Some(q)) if c == q => quote = None,
+            (_, Some(_)) => {}
+            (Some(s), None) if c.is_whitespace() => {
+                spans.push((s, i));
+                start = None;
+            }
+            (_, None) if c.is_whitespace() => {}
+            (s, None) => {
+                if s.is_none() {
+                    start = Some(i);
+                }
+                if c == '"' || c == '\'' {
+                    quote = Some(c);
+                }
+            }
+        }
+    }
+    if let Some(s) = start {
+        spans.push((s, line.len()));
+    }
+    spans
+}
+
+fn value_name(arg: &Arg) -> String {
+    let name = arg
+        .get_value_names()
+        .and_then(|names| names.first())
+        .map(|name| name.to_string())
+        .unwrap_or_else(|| arg.get_id().as_str().to_uppercase());
+    format!("<{}>", name)
+}
+
+/// Finds the argument a `--long` or `-s` flag refers to, global flags live on the root
+fn find_arg<'a>(root: &'a Command, command: &'a Command, flag: &str) -> Option<&'a Arg> {
+    let matches = |arg: &&Arg| match flag.strip_prefix("--") {
+        Some(long) => arg.get_long() == Some(long),
+        None => flag
+            .strip_prefix('-')
+            .and_then(|short| short.chars().next())
+            .is_some_and(|short| arg.get_short() == Some(short)),
+    };
+    command
+        .get_arguments()
+        .find(matches)
+        .or_else(|| root.get_arguments().find(matches))
+}
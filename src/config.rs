@@ -1,36 +1,137 @@
 use directories::ProjectDirs;
-use serde::{Deserialize, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet};
 use std::fs;
 use std::io::Result;
 use std::path::PathBuf;
+use std::sync::OnceLock;
 
-#[derive(Debug, Serialize, Deserialize)]
+use crate::vault::{ProfileTokens, Vault};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Agent {
     pub id: String,
+    /// Kept in the token vault, only read here when migrating a plaintext config
+    #[serde(default, skip_serializing)]
     pub token: String,
+    /// Faction the agent was registered with, used to re-register it after a server reset
+    #[serde(default)]
+    pub faction: Option<String>,
+    /// Server reset date when the agent was registered, its token stops working once
+    /// the server resets again
+    #[serde(default)]
+    pub reset_date: Option<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+/// Account token used instead of the stored one
+pub const ACCOUNT_TOKEN_ENV: &str = "SPACETRADERS_ACCOUNT_TOKEN";
+/// Prefix of per agent token variables, followed by the callsign in upper case with
+/// dashes written as underscores. Agents not in the config take the callsign the
+/// server reports once loaded.
+pub const AGENT_TOKEN_ENV_PREFIX: &str = "SPACETRADERS_AGENT_TOKEN_";
+
+/// Config file chosen with `--config`, the config dir is used when unset
+static CONFIG_PATH: OnceLock<PathBuf> = OnceLock::new();
+
+/// Profile used when `--profile` is not given, configs from before profiles existed
+/// are moved into it
+pub const DEFAULT_PROFILE: &str = "prod";
+/// `--base-url` value that points a profile back at the public API
+pub const PUBLIC_API: &str = "public";
+
+/// Settings for one server, each profile keeps its own token and agents
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
+    /// Name of the profile these settings are stored under
+    #[serde(skip)]
+    pub profile: String,
+    /// Kept in the token vault, only read here when migrating a plaintext config
+    #[serde(default, skip_serializing)]
     pub account_token: String,
     pub agents: Vec<Agent>,
+    /// Reset date reported by the server at the last startup, used for agents stored
+    /// before each agent kept its own
+    #[serde(default)]
+    pub reset_date: Option<String>,
+    /// Server to talk to instead of the public SpaceTraders API
+    #[serde(default)]
+    pub base_url: Option<String>,
+    /// REPL aliases, each name expands to the command line stored for it
+    #[serde(default)]
+    pub aliases: BTreeMap<String, String>,
+    /// Tokens replaced by the environment or flags for this run, never saved
+    #[serde(skip)]
+    overrides: TokenOverrides,
 }
 
-impl Config {
-    pub fn load() -> Result<Self> {
+#[derive(Debug, Clone, Default)]
+struct TokenOverrides {
+    account: bool,
+    /// Callsigns of configured agents whose token was replaced
+    agents: BTreeSet<String>,
+    /// Callsigns only known from the environment
+    added: BTreeSet<String>,
+}
+
+impl TokenOverrides {
+    fn covers(&self, callsign: &str) -> bool {
+        self.agents.contains(callsign) || self.added.contains(callsign)
+    }
+}
+
+/// Layout of config.json, every profile keyed by name
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct ConfigFile {
+    profiles: BTreeMap<String, Config>,
+}
+
+impl ConfigFile {
+    fn load() -> Result<Self> {
         let path = config_path();
-        if path.exists() {
-            let contents = fs::read_to_string(path)?;
-            Ok(serde_json::from_str(&contents)?)
+        if !path.exists() {
+            return Ok(ConfigFile::default());
+        }
+        let contents: Value = serde_json::from_str(&fs::read_to_string(path)?)?;
+        if contents.get("profiles").is_some() {
+            Ok(serde_json::from_value(contents)?)
         } else {
-            Ok(Config {
-                account_token: String::new(),
-                agents: Vec::new(),
+            let legacy: Config = serde_json::from_value(contents)?;
+            Ok(ConfigFile {
+                profiles: BTreeMap::from([(DEFAULT_PROFILE.to_string(), legacy)]),
             })
         }
     }
 
-    pub fn save(&self) -> Result<()> {
+    fn has_plaintext_tokens(&self) -> bool {
+        self.profiles.values().any(|config| {
+            !config.account_token.is_empty()
+                || config.agents.iter().any(|agent| !agent.token.is_empty())
+        })
+    }
+
+    /// Moves tokens from a config written before the vault existed into the vault.
+    /// Tokens already in the vault are kept, they are newer than ones left in the config.
+    fn migrate_tokens(&self) -> Result<()> {
+        eprintln!("Moving tokens from config.json into the encrypted token vault");
+        let mut vault = Vault::load()?;
+        for (profile, config) in &self.profiles {
+            let tokens = vault.profiles.entry(profile.clone()).or_default();
+            if tokens.account_token.is_empty() {
+                tokens.account_token = config.account_token.clone();
+            }
+            for agent in &config.agents {
+                tokens
+                    .agents
+                    .entry(agent.id.clone())
+                    .or_insert_with(|| agent.token.clone());
+            }
+        }
+        vault.save()?;
+        self.save()
+    }
+
+    fn save(&self) -> Result<()> {
         let path = config_path();
         if let Some(parent) = path.parent() {
             fs::create_dir_all(parent)?;
@@ -41,9 +142,279 @@ impl Config {
     }
 }
 
-fn config_path() -> PathBuf {
+impl Config {
+    pub fn new(profile: &str) -> Self {
+        Config {
+            profile: profile.to_string(),
+            account_token: String::new(),
+            agents: Vec::new(),
+            reset_date: None,
+            base_url: None,
+            aliases: BTreeMap::new(),
+            overrides: TokenOverrides::default(),
+        }
+    }
+
+    /// Loads the named profile, an unknown profile starts out empty
+    pub fn load(profile: &str) -> Result<Self> {
+        let mut config = Config::read(profile)?;
+        config.fill_tokens()?;
+        Ok(config)
+    }
+
+    /// Loads the named profile with tokens given by `--account-token` or the environment
+    /// in place of stored ones. The vault is only unlocked for tokens that are still
+    /// missing, so a headless run given every token never asks for the passphrase.
+    pub fn load_with_overrides(profile: &str, account_token: Option<&str>) -> Result<Self> {
+        let mut config = Config::read(profile)?;
+        config.apply_overrides(account_token);
+        config.fill_tokens()?;
+        Ok(config)
+    }
+
+    fn read(profile: &str) -> Result<Self> {
+        let mut file = ConfigFile::load()?;
+        if file.has_plaintext_tokens() {
+            file.migrate_tokens()?;
+        }
+        let mut config = match file.profiles.remove(profile) {
+            Some(mut config) => {
+                config.profile = profile.to_string();
+                config
+            }
+            None => Config::new(profile),
+        };
+        Ok(config)
+    }
+
+    /// Whether any token this profile uses is kept in the vault rather than overridden
+    fn uses_vault(&self) -> bool {
+        !self.overrides.account
+            || self
+                .agents
+                .iter()
+                .any(|agent| !self.overrides.covers(&agent.id))
+    }
+
+    /// Takes the tokens that were not overridden from the vault
+    fn fill_tokens(&mut self) -> Result<()> {
+        if !self.uses_vault() || !Vault::exists() {
+            return Ok(());
+        }
+        let vault = Vault::load()?;
+        let Some(tokens) = vault.profiles.get(&self.profile) else {
+            return Ok(());
+        };
+        if !self.overrides.account {
+            self.account_token = tokens.account_token.clone();
+        }
+        for agent in &mut self.agents {
+            if !self.overrides.covers(&agent.id) {
+                agent.token = tokens.agents.get(&agent.id).cloned().unwrap_or_default();
+            }
+        }
+        Ok(())
+    }
+
+    /// Replaces tokens with ones given by `--account-token` or the environment. Agents
+    /// only named by an environment variable are added for this run under the variable's
+    /// name until `rename_added_agent` gives them the callsign the server reports.
+    fn apply_overrides(&mut self, account_token: Option<&str>) {
+        if let Some(token) = account_token
+            .map(str::to_string)
+            .or_else(|| std::env::var(ACCOUNT_TOKEN_ENV).ok())
+        {
+            self.account_token = token;
+            self.overrides.account = true;
+        }
+
+        for (name, token) in std::env::vars() {
+            let Some(callsign) = name.strip_prefix(AGENT_TOKEN_ENV_PREFIX) else {
+                continue;
+            };
+            match self
+                .agents
+                .iter_mut()
+                .find(|agent| agent.id.to_uppercase().replace('-', "_") == callsign)
+            {
+                Some(agent) => {
+                    agent.token = token;
+                    self.overrides.agents.insert(agent.id.clone());
+                }
+                None => {
+                    self.agents.push(Agent {
+                        id: callsign.to_string(),
+                        token,
+                        faction: None,
+                        reset_date: None,
+                    });
+                    self.overrides.added.insert(callsign.to_string());
+                }
+            }
+        }
+    }
+
+    /// Writes this profile back to the config file and its tokens to the vault, leaving
+    /// other profiles untouched. Overridden tokens keep their stored value.
+    pub fn save(&self) -> Result<()> {
+        let mut stored = self.clone();
+        stored
+            .agents
+            .retain(|agent| !self.overrides.added.contains(&agent.id));
+        // With every token overridden the vault has nothing to update
+        if self.uses_vault() {
+            self.save_tokens(&mut stored)?;
+        }
+
+        let mut file = ConfigFile::load()?;
+        file.profiles.insert(self.profile.clone(), stored);
+        file.save()
+    }
+
+    /// Stores the tokens of a profile about to be saved, putting back the stored value
+    /// of every overridden one
+    fn save_tokens(&self, stored: &mut Config) -> Result<()> {
+        let mut vault = Vault::load()?;
+        let previous = vault.profiles.get(&self.profile);
+        if self.overrides.account {
+            stored.account_token = previous
+                .map(|tokens| tokens.account_token.clone())
+                .unwrap_or_default();
+        }
+        for agent in &mut stored.agents {
+            if self.overrides.agents.contains(&agent.id) {
+                agent.token = previous
+                    .and_then(|tokens| tokens.agents.get(&agent.id).cloned())
+                    .unwrap_or_default();
+            }
+        }
+
+        let tokens = ProfileTokens::from(&*stored);
+        let unchanged = match previous {
+            Some(previous) => *previous == tokens,
+            None => tokens.is_empty(),
+        };
+        if !unchanged {
+            vault.profiles.insert(self.profile.clone(), tokens);
+            vault.save()?;
+        }
+        Ok(())
+    }
+
+    /// Callsigns of agents only known from the environment, named after their variables
+    pub fn added_agents(&self) -> Vec<String> {
+        self.overrides.added.iter().cloned().collect()
+    }
+
+    /// Whether an agent's token comes from the environment rather than the vault
+    pub fn token_overridden(&self, callsign: &str) -> bool {
+        self.overrides.covers(callsign)
+    }
+
+    /// The variable name an agent added from the environment is kept under until it is
+    /// first loaded, if the callsign belongs to one
+    pub fn added_agent_name(&self, callsign: &str) -> Option<String> {
+        let name = callsign.to_uppercase().replace('-', "_");
+        self.overrides.added.contains(&name).then_some(name)
+    }
+
+    /// Moves an agent added from the environment to the callsign the server reports.
+    /// Variable names only hold upper case letters, digits and underscores, so they
+    /// cannot tell a dash from an underscore in a callsign.
+    pub fn rename_added_agent(&mut self, name: &str, callsign: &str) {
+        if !self.overrides.added.remove(name) {
+            return;
+        }
+        if let Some(agent) = self.agents.iter_mut().find(|agent| agent.id == name) {
+            agent.id = callsign.to_string();
+        }
+        self.overrides.added.insert(callsign.to_string());
+    }
+}
+
+impl From<&Config> for ProfileTokens {
+    fn from(config: &Config) -> Self {
+        ProfileTokens {
+            account_token: config.account_token.clone(),
+            agents: config
+                .agents
+                .iter()
+                .map(|agent| (agent.id.clone(), agent.token.clone()))
+                .collect(),
+        }
+    }
+}
+
+/// Overrides where config and cached data are kept, used to isolate test runs
+pub const CONFIG_DIR_ENV: &str = "SPACE_TRADERS_CLI_CONFIG_DIR";
+
+/// Where config and cached data are kept, next to the file given with `--config` if any
+pub fn config_dir() -> PathBuf {
+    if let Some(path) = CONFIG_PATH.get() {
+        return match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+            _ => PathBuf::from("."),
+        };
+    }
+    if let Some(dir) = std::env::var_os(CONFIG_DIR_ENV) {
+        return PathBuf::from(dir);
+    }
     ProjectDirs::from("com", "CollinDietz", "space-traders-cli")
         .expect("No valid home directory found")
         .config_dir()
-        .join("config.json")
+        .to_path_buf()
+}
+
+/// Where a profile keeps its surveys, market cache and REPL history. The default
+/// profile uses the config dir itself so data from before profiles existed stays with it.
+pub fn profile_dir(profile: &str) -> PathBuf {
+    if profile == DEFAULT_PROFILE {
+        config_dir()
+    } else {
+        config_dir().join("profiles").join(profile)
+    }
+}
+
+/// A JSON file in a profile's directory holding data kept between sessions
+#[derive(Debug, Default, Clone)]
+pub struct ProfileFile {
+    path: PathBuf,
+}
+
+impl ProfileFile {
+    pub fn new(profile: &str, name: &str) -> Self {
+        ProfileFile {
+            path: profile_dir(profile).join(name),
+        }
+    }
+
+    /// Reads the file, the default value is used when it has not been written yet
+    pub fn load<T: DeserializeOwned + Default>(&self) -> Result<T> {
+        if !self.path.exists() {
+            return Ok(T::default());
+        }
+        let contents = fs::read_to_string(&self.path)?;
+        Ok(serde_json::from_str(&contents)?)
+    }
+
+    pub fn save<T: Serialize>(&self, value: &T) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let contents = serde_json::to_string_pretty(value)?;
+        fs::write(&self.path, contents)
+    }
+}
+
+/// Uses the given config file for the rest of the run, the token vault and profile data
+/// are kept next to it
+pub fn set_config_path(path: PathBuf) {
+    let _ = CONFIG_PATH.set(path);
+}
+
+pub fn config_path() -> PathBuf {
+    CONFIG_PATH
+        .get()
+        .cloned()
+        .unwrap_or_else(|| config_dir().join("config.json"))
 }
@@ -0,0 +1,15 @@
+pub mod application;
+pub mod cli;
+pub mod completion;
+pub mod config;
+pub mod market_cache;
+pub mod output;
+pub mod rate_limiter;
+pub mod repl;
+pub mod survey_store;
+pub mod table;
+pub mod travel;
+pub mod utils;
+pub mod vault;
+
+pub use application::Application;
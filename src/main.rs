@@ -1,65 +1,90 @@
-use std::{collections::HashMap, sync::Arc};
+use std::path::PathBuf;
 
 use clap::Parser;
-use config::Config;
-use space_traders_sdk::{account::Account, agent::Agent, space_traders_client::SpaceTradersClient};
-
-mod cli;
-mod config;
-mod repl;
-mod utils;
+use space_traders_cli::{
+    cli,
+    config::{self, Config},
+    market_cache::MarketCache,
+    output::OutputFormat,
+    repl,
+    survey_store::SurveyStore,
+    Application,
+};
 
 #[derive(Parser)]
 #[command(name = "Space Traders CLI")]
 #[command(about = "A CLI tool to interact with Space Traders SDK", long_about = None)]
 struct Cli {
+    /// Format to print command results in
+    #[arg(long, global = true, value_enum)]
+    output: Option<OutputFormat>,
+
+    /// Config profile holding the server, account token and agents to use
+    #[arg(long, global = true, default_value = config::DEFAULT_PROFILE)]
+    profile: String,
+
+    /// Account token to use for this run instead of the stored one, never saved
+    #[arg(long, visible_alias = "token")]
+    account_token: Option<String>,
+
+    /// Server for the profile instead of the public API, stored with the profile. `public`
+    /// switches it back to the public API, the default profile always uses it.
+    #[arg(long, global = true)]
+    base_url: Option<String>,
+
+    /// Config file to use instead of the one in the config directory
+    #[arg(long, global = true)]
+    config: Option<PathBuf>,
+
     #[command(subcommand)]
     command: Option<cli::Commands>,
 }
 
 use rpassword::prompt_password;
-use std::io::{self, Write};
-
-pub struct Application {
-    pub config: Config,
-    pub client: Arc<SpaceTradersClient>,
-    pub account: Account,
-    pub agents: HashMap<String, Agent>,
-}
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    let mut config = Config::load()?;
+    let cli = Cli::parse();
+    if let Some(path) = &cli.config {
+        config::set_config_path(path.clone());
+    }
+    let mut config = Config::load_with_overrides(&cli.profile, cli.account_token.as_deref())?;
+    if let Some(base_url) = &cli.base_url {
+        let base_url = (base_url != config::PUBLIC_API).then(|| base_url.clone());
+        if cli.profile == config::DEFAULT_PROFILE && base_url.is_some() {
+            anyhow::bail!(
+                "Profile {} always uses the public API, pick another one with --profile",
+                config::DEFAULT_PROFILE
+            );
+        }
+        if base_url != config.base_url {
+            config.base_url = base_url;
+            config.save()?;
+        }
+    }
 
     if config.account_token.is_empty() {
+        // Other profiles are usually for other servers, so the public API is never assumed
+        if cli.profile != config::DEFAULT_PROFILE && config.base_url.is_none() {
+            anyhow::bail!(
+                "Profile {} has no server yet, choose one with --base-url",
+                cli.profile
+            );
+        }
         println!("No token found. Please enter your API token:");
         let token = prompt_password("Token: ")?;
         config.account_token = token.trim().to_string();
         config.save()?;
     }
 
-    let client = Arc::new(SpaceTradersClient::new(Some(config.account_token.clone())));
-
-    print!("Loading details for known agents...");
-    io::stdout().flush().unwrap();
-    let agent_futures = config.agents.iter().map(|agent| {
-        let client = Arc::new(SpaceTradersClient::clone_with_token(&client, &agent.token));
-        let id = agent.id.clone();
-        async move { (id, Agent::new(client).await.unwrap()) }
-    });
-
-    let agents_vec = futures::future::join_all(agent_futures).await;
-    let agents: HashMap<String, Agent> = agents_vec.into_iter().collect();
-    println!("done");
-
-    let mut application = Application {
-        account: Account::new(client.clone()),
+    let mut application = Application::new(
         config,
-        client,
-        agents,
-    };
+        SurveyStore::load(&cli.profile)?,
+        MarketCache::load(&cli.profile)?,
+        cli.output.unwrap_or_default(),
+    );
+    application.check_server_reset().await?;
 
-    let cli = Cli::parse();
     match cli.command {
         Some(cmd) => cli::handle_command(cmd, &mut application).await?,
         None => repl::start(&mut application).await?,
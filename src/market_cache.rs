@@ -0,0 +1,65 @@
+use serde::{Deserialize, Serialize};
+use space_traders_sdk::market::{MarketData, MarketTradeGood, TradeSymbol};
+use std::collections::HashMap;
+use std::io::Result;
+
+use crate::{config::ProfileFile, travel::system_symbol};
+
+/// Last seen market data with prices, keyed by waypoint symbol
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct MarketCache {
+    #[serde(skip)]
+    file: ProfileFile,
+    pub markets: HashMap<String, MarketData>,
+}
+
+impl MarketCache {
+    /// An empty cache saved to the profile's markets.json
+    pub fn new(profile: &str) -> Self {
+        MarketCache {
+            file: ProfileFile::new(profile, "markets.json"),
+            ..Default::default()
+        }
+    }
+
+    pub fn load(profile: &str) -> Result<Self> {
+        let file = ProfileFile::new(profile, "markets.json");
+        Ok(MarketCache {
+            file: file.clone(),
+            ..file.load()?
+        })
+    }
+
+    pub fn save(&self) -> Result<()> {
+        self.file.save(self)
+    }
+
+    /// Records a market, only markets with visible prices are worth keeping
+    pub fn update(&mut self, market: &MarketData) -> Result<()> {
+        if market.trade_goods.is_some() {
+            self.markets.insert(market.symbol.clone(), market.clone());
+            self.save()?;
+        }
+        Ok(())
+    }
+
+    /// The cached market in a system selling a good for the lowest price
+    pub fn cheapest_purchase(
+        &self,
+        good: &TradeSymbol,
+        system: &str,
+    ) -> Option<(&str, &MarketTradeGood)> {
+        self.markets
+            .values()
+            .filter(|market| system_symbol(&market.symbol) == system)
+            .filter_map(|market| {
+                market
+                    .trade_goods
+                    .iter()
+                    .flatten()
+                    .find(|trade_good| &trade_good.symbol == good)
+                    .map(|trade_good| (market.symbol.as_str(), trade_good))
+            })
+            .min_by_key(|(_, trade_good)| trade_good.purchase_price)
+    }
+}
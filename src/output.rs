@@ -0,0 +1,111 @@
+use clap::ValueEnum;
+use serde::Serialize;
+use serde_json::Value;
+
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq)]
+pub enum OutputFormat {
+    /// Human readable text
+    #[default]
+    Text,
+    Json,
+    Yaml,
+    Csv,
+}
+
+/// Renders a command's result in the selected format, `text` prints the human readable view
+pub fn render<T: Serialize + ?Sized>(
+    format: OutputFormat,
+    value: &T,
+    text: impl FnOnce(),
+) -> anyhow::Result<()> {
+    match format {
+        OutputFormat::Text => text(),
+        OutputFormat::Json => print_json_value(&serde_json::to_value(value)?)?,
+        OutputFormat::Yaml => print!("{}", serde_yaml::to_string(value)?),
+        OutputFormat::Csv => print!("{}", csv(&serde_json::to_value(value)?)),
+    }
+    Ok(())
+}
+
+pub fn print_json_value(value: &Value) -> anyhow::Result<()> {
+    println!("{}", serde_json::to_string_pretty(value)?);
+    Ok(())
+}
+
+/// Writes arrays of objects as one row per element, anything else as a single row,
+/// nested values are written as compact JSON within their cell
+fn csv(value: &Value) -> String {
+    let rows: Vec<&Value> = match value {
+        Value::Array(items) => items.iter().collect(),
+        other => vec![other],
+    };
+
+    let mut columns: Vec<&String> = Vec::new();
+    for row in &rows {
+        if let Value::Object(fields) = row {
+            for key in fields.keys() {
+                if !columns.contains(&key) {
+                    columns.push(key);
+                }
+            }
+        }
+    }
+
+    let mut lines = Vec::new();
+    if columns.is_empty() {
+        lines.extend(rows.into_iter().map(csv_field));
+    } else {
+        let header: Vec<_> = columns.iter().map(|c| csv_escape(c)).collect();
+        lines.push(header.join(","));
+        for row in rows {
+            let fields: Vec<_> = columns
+                .iter()
+                .map(|column| row.get(column.as_str()).map(csv_field).unwrap_or_default())
+                .collect();
+            lines.push(fields.join(","));
+        }
+    }
+    lines.iter().map(|line| format!("{}\n", line)).collect()
+}
+
+fn csv_field(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(s) => csv_escape(s),
+        other => csv_escape(&other.to_string()),
+    }
+}
+
+fn csv_escape(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn csv_quotes_fields_with_commas_quotes_and_line_breaks() {
+        let value = json!([
+            { "name": "Market, North", "note": "say \"hi\"", "symbol": "X1-AB-C1" },
+            { "note": "two\nlines", "symbol": "X1-AB-C2", "traits": ["A", "B"] },
+        ]);
+        assert_eq!(
+            csv(&value),
+            "name,note,symbol,traits\n\
+             \"Market, North\",\"say \"\"hi\"\"\",X1-AB-C1,\n\
+             ,\"two\nlines\",X1-AB-C2,\"[\"\"A\"\",\"\"B\"\"]\"\n"
+        );
+    }
+
+    #[test]
+    fn csv_writes_other_values_as_a_single_row() {
+        assert_eq!(csv(&json!({ "removed": 3 })), "removed\n3\n");
+        assert_eq!(csv(&json!(["a,b", null, 4])), "\"a,b\"\n\n4\n");
+    }
+}
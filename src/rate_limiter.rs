@@ -0,0 +1,128 @@
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use serde_json::Value;
+use space_traders_sdk::space_traders_client::Error;
+
+/// Sustained requests per second allowed by the server for an account
+pub const REQUESTS_PER_SECOND: f64 = 2.0;
+/// Requests that may be sent back to back before the sustained rate applies
+pub const BURST: u32 = 10;
+/// Times a rate limited request is retried before its error is returned
+const MAX_RETRIES: u32 = 3;
+/// Wait used when a 429 response does not say how long to back off
+const DEFAULT_RETRY_AFTER: Duration = Duration::from_secs(1);
+
+/// Token bucket shared by every client made from the base client, so requests from
+/// all agents count against the same account limit. Every SDK call goes through it
+/// with `limited!`.
+pub struct RateLimiter {
+    rate: f64,
+    burst: f64,
+    bucket: Mutex<Bucket>,
+}
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl RateLimiter {
+    pub fn new(rate: f64, burst: u32) -> Self {
+        RateLimiter {
+            rate,
+            burst: burst as f64,
+            bucket: Mutex::new(Bucket {
+                tokens: burst as f64,
+                updated: Instant::now(),
+            }),
+        }
+    }
+
+    /// Waits until a request may be sent and takes its token
+    pub async fn acquire(&self) {
+        loop {
+            let wait = {
+                let mut bucket = self.bucket.lock().unwrap();
+                let now = Instant::now();
+                let elapsed = now.duration_since(bucket.updated).as_secs_f64();
+                bucket.tokens = (bucket.tokens + elapsed * self.rate).min(self.burst);
+                bucket.updated = now;
+                if bucket.tokens >= 1.0 {
+                    bucket.tokens -= 1.0;
+                    return;
+                }
+                Duration::from_secs_f64((1.0 - bucket.tokens) / self.rate)
+            };
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    /// How long to wait before retrying a request that failed with the given error, if
+    /// it was rate limited and has retries left. The server sends `retryAfter` in seconds
+    /// within the error data of its 429 responses.
+    pub fn backoff(&self, error: &Error, attempt: u32) -> Option<Duration> {
+        if attempt >= MAX_RETRIES || error.status() != Some(429) {
+            return None;
+        }
+        Some(
+            error
+                .data()
+                .and_then(|data| data.get("retryAfter"))
+                .and_then(Value::as_f64)
+                .map(Duration::from_secs_f64)
+                .unwrap_or(DEFAULT_RETRY_AFTER),
+        )
+    }
+}
+
+/// Sends an SDK request through a rate limiter, retrying after the server's
+/// `retryAfter` when it answers 429. The request expression is evaluated again for
+/// each attempt, so it may borrow ships and contracts mutably. Calls that make several
+/// requests pass how many, so each one takes a token.
+macro_rules! limited {
+    ($limiter:expr, $request:expr) => {
+        $crate::rate_limiter::limited!($limiter, $request, 1)
+    };
+    ($limiter:expr, $request:expr, $requests:expr) => {{
+        let mut attempt = 0;
+        loop {
+            for _ in 0..$requests {
+                $limiter.acquire().await;
+            }
+            let result = $request.await;
+            let wait = result
+                .as_ref()
+                .err()
+                .and_then(|e| $limiter.backoff(e, attempt));
+            match wait {
+                Some(wait) => {
+                    attempt += 1;
+                    tokio::time::sleep(wait).await;
+                }
+                None => break result,
+            }
+        }
+    }};
+}
+pub(crate) use limited;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn requests_wait_once_the_burst_is_spent() {
+        let limiter = RateLimiter::new(10.0, 3);
+        let started = Instant::now();
+        for _ in 0..3 {
+            limiter.acquire().await;
+        }
+        assert!(started.elapsed() < Duration::from_millis(50));
+
+        limiter.acquire().await;
+        assert!(started.elapsed() >= Duration::from_millis(90));
+    }
+}
@@ -1,7 +1,8 @@
-use std::path::PathBuf;
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
 
 use clap::{CommandFactory, Parser};
-use directories::ProjectDirs;
 use rustyline::completion::{Completer, Pair};
 use rustyline::highlight::Highlighter;
 use rustyline::hint::Hinter;
@@ -9,10 +10,18 @@ use rustyline::validate::{ValidationContext, ValidationResult, Validator};
 use rustyline::{error::ReadlineError, Editor};
 use rustyline::{Context, Helper};
 
-use crate::Application;
+use crate::{
+    cli::Commands,
+    completion::{self, CompletionValues, REPL_COMMANDS},
+    config::profile_dir,
+    Application,
+};
 
 pub struct ReplHelper {
-    pub commands: Vec<String>,
+    pub command: clap::Command,
+    pub values: CompletionValues,
+    /// Arguments the session context fills in, so hints do not ask for them
+    pub provided: Vec<&'static str>,
 }
 
 impl Completer for ReplHelper {
@@ -20,29 +29,43 @@ impl Completer for ReplHelper {
     fn complete(
         &self,
         line: &str,
-        _pos: usize,
+        pos: usize,
         _ctx: &Context<'_>,
     ) -> rustyline::Result<(usize, Vec<Self::Candidate>)> {
-        let completions = self
-            .commands
-            .iter()
-            .filter(|cmd| cmd.starts_with(line))
-            .map(|cmd| Pair {
-                display: cmd.clone(),
-                replacement: cmd.clone(),
+        let (start, candidates) = completion::complete(&self.command, &self.values, &line[..pos]);
+        let completions = candidates
+            .into_iter()
+            .map(|candidate| Pair {
+                display: candidate.clone(),
+                replacement: candidate,
             })
             .collect();
-        Ok((0, completions))
+        Ok((start, completions))
     }
 }
 
 impl Hinter for ReplHelper {
     type Hint = String;
-    fn hint(&self, _line: &str, _pos: usize, _ctx: &Context<'_>) -> Option<String> {
-        None
+    fn hint(&self, line: &str, pos: usize, _ctx: &Context<'_>) -> Option<String> {
+        if pos < line.len() {
+            return None;
+        }
+        completion::hint(&self.command, &self.values, line, &self.provided)
+    }
+}
+impl Highlighter for ReplHelper {
+    fn highlight<'l>(&self, line: &'l str, _pos: usize) -> Cow<'l, str> {
+        Cow::Owned(completion::highlight(&self.command, &self.values, line))
+    }
+
+    fn highlight_hint<'h>(&self, hint: &'h str) -> Cow<'h, str> {
+        Cow::Owned(format!("\x1b[90m{}\x1b[0m", hint))
+    }
+
+    fn highlight_char(&self, _line: &str, _pos: usize, _forced: bool) -> bool {
+        true
     }
 }
-impl Highlighter for ReplHelper {}
 impl Validator for ReplHelper {
     fn validate(&self, _ctx: &mut ValidationContext<'_>) -> rustyline::Result<ValidationResult> {
         Ok(ValidationResult::Valid(None))
@@ -50,53 +73,170 @@ impl Validator for ReplHelper {
 }
 impl Helper for ReplHelper {}
 
-fn history_path() -> PathBuf {
-    let path = ProjectDirs::from("com", "CollinDietz", "space-traders-cli")
-        .expect("No valid home directory found")
-        .config_dir()
-        .join("history.txt");
+/// Returned when Ctrl-C cancels the running command. Tokio keeps its SIGINT handler
+/// for the rest of the process once one is installed, so every command the REPL runs
+/// listens for Ctrl-C rather than only the ones expected to take long.
+#[derive(Debug)]
+pub struct Interrupted;
 
-    if let Some(parent) = path.parent() {
-        std::fs::create_dir_all(parent).expect("Failed to create config directory");
+impl std::fmt::Display for Interrupted {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Interrupted")
     }
+}
 
-    path
+impl std::error::Error for Interrupted {}
+
+/// Defaults chosen with `use`, filled into commands that leave them out
+#[derive(Debug, Default)]
+pub struct Session {
+    pub callsign: Option<String>,
+    pub ship: Option<String>,
+    /// Scripts being run, innermost last, so a script cannot source itself
+    scripts: Vec<PathBuf>,
 }
 
-pub async fn start(application: &mut Application) -> anyhow::Result<()> {
-    fn collect_command_names(cmd: &clap::Command, prefix: String, names: &mut Vec<String>) {
-        // Collect flags/options for this command
-        for opt in cmd.get_opts() {
-            let flag = if let Some(short) = opt.get_short() {
-                format!("{} -{}", prefix, short)
-            } else if let Some(long) = opt.get_long() {
-                format!("{} --{}", prefix, long)
-            } else {
+impl Session {
+    /// Ids of the arguments this context fills in
+    fn provided(&self) -> Vec<&'static str> {
+        let mut provided = Vec::new();
+        if self.callsign.is_some() {
+            provided.push("callsign");
+        }
+        if self.ship.is_some() {
+            provided.push("ship");
+        }
+        provided
+    }
+
+    fn prompt(&self) -> String {
+        match (&self.callsign, &self.ship) {
+            (Some(callsign), Some(ship)) => format!("{} {} >> ", callsign, ship),
+            (Some(callsign), None) => format!("{} >> ", callsign),
+            (None, _) => String::from(">> "),
+        }
+    }
+
+    /// Handles `use <callsign>`, `use ship <symbol>` and `unuse`
+    fn update(&mut self, application: &Application, args: &[String]) {
+        match args {
+            [command, ..] if command == "unuse" => {
+                self.callsign = None;
+                self.ship = None;
+                println!("Cleared the session context");
+            }
+            [_] => match &self.callsign {
+                Some(callsign) => println!(
+                    "Using agent {}, ship {}",
+                    callsign,
+                    self.ship.as_deref().unwrap_or("none")
+                ),
+                None => println!("No agent in use"),
+            },
+            [_, kind, ship] if kind == "ship" => {
+                // Ships of agents that are not loaded yet are named after their callsign
+                let owner = application
+                    .agents
+                    .iter()
+                    .find(|(_, agent)| agent.ships().any(|(symbol, _)| symbol == ship))
+                    .map(|(callsign, _)| callsign.clone())
+                    .or_else(|| {
+                        ship.rsplit_once('-')
+                            .map(|(callsign, _)| callsign.to_string())
+                            .filter(|callsign| {
+                                application
+                                    .config
+                                    .agents
+                                    .iter()
+                                    .any(|agent| &agent.id == callsign)
+                                    || application.config.added_agent_name(callsign).is_some()
+                            })
+                    });
+                let Some(owner) = owner else {
+                    println!("No known agent owns ship {}", ship);
+                    return;
+                };
+                self.callsign = Some(owner);
+                self.ship = Some(ship.clone());
+                println!("Using ship {}", ship);
+            }
+            [_, callsign] => {
+                if !application
+                    .config
+                    .agents
+                    .iter()
+                    .any(|agent| &agent.id == callsign)
+                {
+                    println!("No known agent with that callsign");
+                    return;
+                }
+                if self.callsign.as_ref() != Some(callsign) {
+                    self.ship = None;
+                }
+                self.callsign = Some(callsign.clone());
+                println!("Using agent {}", callsign);
+            }
+            _ => println!("Usage: use <callsign> | use ship <symbol> | unuse"),
+        }
+    }
+
+    /// Adds `--callsign` and `--ship` from the context to commands that take them but
+    /// were not given them
+    fn fill_defaults(&self, args: &mut Vec<String>) {
+        let root = crate::cli::ReplCli::command();
+        let mut command = &root;
+        for word in args.iter() {
+            if let Some(sub) = command.find_subcommand(word) {
+                command = sub;
+            }
+        }
+
+        let mut missing = Vec::new();
+        for (id, value) in [("callsign", &self.callsign), ("ship", &self.ship)] {
+            let Some(value) = value else {
                 continue;
             };
-            names.push(flag.trim().to_string());
-        }
-        // Collect subcommands recursively
-        for sub in cmd.get_subcommands() {
-            let name = if prefix.is_empty() {
-                sub.get_name().to_string()
-            } else {
-                format!("{} {}", prefix, sub.get_name())
+            let Some(arg) = command.get_arguments().find(|arg| arg.get_id() == id) else {
+                continue;
             };
-            names.push(name.clone());
-            collect_command_names(sub, name, names);
+            let long = arg.get_long().map(|long| format!("--{}", long));
+            let short = arg.get_short().map(|short| format!("-{}", short));
+            let given = args.iter().any(|word| {
+                Some(word) == long.as_ref()
+                    || Some(word) == short.as_ref()
+                    || long
+                        .as_ref()
+                        .is_some_and(|long| word.starts_with(&format!("{}=", long)))
+            });
+            if let (false, Some(long)) = (given, long) {
+                missing.push(long);
+                missing.push(value.clone());
+            }
         }
+        args.extend(missing);
     }
+}
 
-    let mut commands = Vec::new();
-    let root_cmd = crate::cli::ReplCli::command();
-    collect_command_names(&root_cmd, String::new(), &mut commands);
-    commands.extend(["exit".into(), "help".into()]);
-    let helper = ReplHelper { commands };
+fn history_path(profile: &str) -> PathBuf {
+    let path = profile_dir(profile).join("history.txt");
+
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).expect("Failed to create profile directory");
+    }
+
+    path
+}
+
+pub async fn start(application: &mut Application) -> anyhow::Result<()> {
+    let helper = ReplHelper {
+        command: completion::repl_command(),
+        values: CompletionValues::collect(application),
+        provided: Vec::new(),
+    };
     let mut rl = Editor::new()?;
     rl.set_helper(Some(helper));
 
-    let history_file = history_path();
+    let history_file = history_path(&application.config.profile);
     if history_file.exists() {
         rl.load_history(&history_file)?;
     } else {
@@ -105,15 +245,20 @@ pub async fn start(application: &mut Application) -> anyhow::Result<()> {
 
     println!("Type 'help' or 'exit'.");
 
+    let mut session = Session::default();
     loop {
-        let readline = rl.readline(">> ");
+        let readline = rl.readline(&session.prompt());
         match readline {
             Ok(line) => {
                 let _ = rl.add_history_entry(line.as_str());
-                match handle_input(application, line).await {
+                match handle_input(application, &mut session, line).await {
                     Ok(true) => break, // exit command
                     Ok(false) => {}    // continue REPL
-                    Err(e) => eprintln!("Error: {e}"),
+                    Err(e) => report_error(&e),
+                }
+                if let Some(helper) = rl.helper_mut() {
+                    helper.values = CompletionValues::collect(application);
+                    helper.provided = session.provided();
                 }
             }
             Err(ReadlineError::Interrupted) | Err(ReadlineError::Eof) => break,
@@ -125,9 +270,168 @@ pub async fn start(application: &mut Application) -> anyhow::Result<()> {
     Ok(())
 }
 
+/// Lists aliases, or saves each `name=command` given. Nothing is saved unless every
+/// definition is valid.
+fn define_aliases(application: &mut Application, definitions: &[String]) -> anyhow::Result<()> {
+    if definitions.is_empty() {
+        for (name, command) in &application.config.aliases {
+            println!("{}={}", name, shell_words::quote(command));
+        }
+        return Ok(());
+    }
+
+    let mut aliases = Vec::new();
+    for definition in definitions {
+        let Some((name, command)) = definition.split_once('=') else {
+            match application.config.aliases.get(definition) {
+                Some(command) => println!("{}={}", definition, shell_words::quote(command)),
+                None => println!("No alias named {}", definition),
+            }
+            continue;
+        };
+        let builtin = REPL_COMMANDS.contains(&name)
+            || crate::cli::ReplCli::command()
+                .find_subcommand(name)
+                .is_some();
+        if name.is_empty() || name.contains(char::is_whitespace) || builtin {
+            anyhow::bail!("Invalid alias name '{}'", name);
+        }
+        aliases.push((name.to_string(), command.to_string()));
+    }
+
+    if !aliases.is_empty() {
+        application.config.aliases.extend(aliases);
+        application.config.save()?;
+    }
+    Ok(())
+}
+
+/// Replaces a leading alias with its command line. `$1`, `$2`... take the arguments
+/// given after the alias and any arguments not taken are appended.
+pub fn expand_alias(aliases: &BTreeMap<String, String>, line: &str) -> anyhow::Result<String> {
+    let trimmed = line.trim_start();
+    let (name, rest) = trimmed
+        .split_once(char::is_whitespace)
+        .unwrap_or((trimmed, ""));
+    let Some(command) = aliases.get(name) else {
+        return Ok(line.to_string());
+    };
+    let args = shell_words::split(rest)?;
+
+    let mut expanded = String::new();
+    let mut used = 0;
+    let mut chars = command.chars().peekable();
+    while let Some(c) = chars.next() {
+        let mut digits = String::new();
+        if c == '$' {
+            while let Some(digit) = chars.next_if(char::is_ascii_digit) {
+                digits.push(digit);
+            }
+        }
+        if digits.is_empty() {
+            expanded.push(c);
+            continue;
+        }
+        let index: usize = digits.parse()?;
+        let arg = index
+            .checked_sub(1)
+            .and_then(|i| args.get(i))
+            .ok_or_else(|| anyhow::anyhow!("Alias {} needs an argument for ${}", name, index))?;
+        expanded.push_str(&shell_words::quote(arg));
+        used = used.max(index);
+    }
+    for arg in args.iter().skip(used) {
+        expanded.push(' ');
+        expanded.push_str(&shell_words::quote(arg));
+    }
+    Ok(expanded)
+}
+
+/// Prints a failed line, clap errors carry their own formatting and usage
+fn report_error(error: &anyhow::Error) {
+    match error.downcast_ref::<clap::Error>() {
+        Some(e) => {
+            let _ = e.print();
+        }
+        None => eprintln!("Error: {error}"),
+    }
+}
+
+/// Runs every line of a script through `handle_input`. Blank lines and lines starting
+/// with `#` are skipped, `set -e` stops the script at the first failing line and
+/// `set -x` echoes each line before it runs, `set +e` and `set +x` turn them off again.
+/// Ctrl-C always stops the script, as does a script sourcing one that is still running.
+/// Returns Ok(true) if the script ran "exit".
+pub async fn run_script(
+    application: &mut Application,
+    session: &mut Session,
+    path: &Path,
+) -> anyhow::Result<bool> {
+    let contents = std::fs::read_to_string(path)
+        .map_err(|e| anyhow::anyhow!("Failed to read script {}: {}", path.display(), e))?;
+    let canonical = path.canonicalize()?;
+    if session.scripts.contains(&canonical) {
+        anyhow::bail!("Script {} is already running", path.display());
+    }
+
+    session.scripts.push(canonical);
+    let result = run_lines(application, session, path, &contents).await;
+    session.scripts.pop();
+    result
+}
+
+async fn run_lines(
+    application: &mut Application,
+    session: &mut Session,
+    path: &Path,
+    contents: &str,
+) -> anyhow::Result<bool> {
+    let mut stop_on_error = false;
+    let mut echo = false;
+
+    for (number, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        match line {
+            "" => continue,
+            _ if line.starts_with('#') => continue,
+            "set -e" => stop_on_error = true,
+            "set +e" => stop_on_error = false,
+            "set -x" => echo = true,
+            "set +x" => echo = false,
+            _ => {
+                if echo {
+                    println!("+ {}", line);
+                }
+                match handle_input(application, session, line.to_string()).await {
+                    Ok(true) => return Ok(true),
+                    Ok(false) => {}
+                    Err(e) if e.is::<Interrupted>() => return Err(e),
+                    Err(e) => {
+                        report_error(&e);
+                        if stop_on_error {
+                            anyhow::bail!(
+                                "Stopped {} at line {}: {}",
+                                path.display(),
+                                number + 1,
+                                line
+                            );
+                        }
+                    }
+                }
+            }
+        }
+    }
+    Ok(false)
+}
+
 /// Returns Ok(true) if the user typed "exit", otherwise Ok(false)
-async fn handle_input(application: &mut Application, line: String) -> anyhow::Result<bool> {
-    let args = shell_words::split(&line)?;
+async fn handle_input(
+    application: &mut Application,
+    session: &mut Session,
+    line: String,
+) -> anyhow::Result<bool> {
+    let line = expand_alias(&application.config.aliases, &line)?;
+    let mut args = shell_words::split(&line)?;
     if args.is_empty() {
         return Ok(false);
     }
@@ -138,20 +442,65 @@ async fn handle_input(application: &mut Application, line: String) -> anyhow::Re
         println!("Available commands:");
         crate::cli::ReplCli::command().print_help()?;
         println!();
+        println!("Session commands:");
+        println!("  use <callsign>      Fill in --callsign for following commands");
+        println!("  use ship <symbol>   Fill in --ship for following commands");
+        println!("  unuse               Clear the session context");
+        println!("  source <file>       Run the commands in a script file");
+        println!(
+            "  alias name=\"...\"    Save a command line under a name, $1, $2... take arguments"
+        );
+        println!("  unalias <name>      Remove an alias");
+        return Ok(false);
+    } else if args[0] == "use" || args[0] == "unuse" {
+        session.update(application, &args);
+        return Ok(false);
+    } else if args[0] == "alias" {
+        define_aliases(application, &args[1..])?;
         return Ok(false);
+    } else if args[0] == "unalias" {
+        for name in &args[1..] {
+            if application.config.aliases.remove(name).is_none() {
+                println!("No alias named {}", name);
+            }
+        }
+        application.config.save()?;
+        return Ok(false);
+    } else if args[0] == "source" {
+        let [_, file] = args.as_slice() else {
+            anyhow::bail!("Usage: source <file>");
+        };
+        return Box::pin(run_script(application, session, Path::new(file))).await;
     }
 
+    session.fill_defaults(&mut args);
+
     match crate::cli::ReplCli::try_parse_from(
         std::iter::once("repl").chain(args.iter().map(String::as_str)),
     ) {
         Ok(parsed) => {
             if let Some(cmd) = parsed.command {
-                crate::cli::handle_command(cmd, application).await?;
+                let session_output = application.output;
+                application.output = parsed.output.unwrap_or(session_output);
+                let result = match cmd {
+                    // Scripts run from the REPL share its session, the same as `source`
+                    Commands::Run { file } => {
+                        Box::pin(run_script(application, session, &file)).await
+                    }
+                    cmd => tokio::select! {
+                        result = crate::cli::handle_command(cmd, application) => {
+                            result.map(|()| false)
+                        }
+                        _ = tokio::signal::ctrl_c() => Err(Interrupted.into()),
+                    },
+                };
+                application.output = session_output;
+                return result;
             }
         }
-        Err(e) => {
-            eprintln!("{e}");
-        }
+        // Help and version output come back as errors that are not failures
+        Err(e) if !e.use_stderr() => e.print()?,
+        Err(e) => return Err(e.into()),
     }
 
     Ok(false)
@@ -0,0 +1,204 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use space_traders_sdk::{
+    market::TradeSymbol,
+    survey::{Survey, SurveySize},
+};
+use std::collections::HashMap;
+use std::io::Result;
+
+use crate::config::ProfileFile;
+
+/// Surveys saved between sessions, keyed by the waypoint they were taken at
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct SurveyStore {
+    #[serde(skip)]
+    file: ProfileFile,
+    pub surveys: HashMap<String, Vec<Survey>>,
+}
+
+impl SurveyStore {
+    /// An empty store saved to the profile's surveys.json
+    pub fn new(profile: &str) -> Self {
+        SurveyStore {
+            file: ProfileFile::new(profile, "surveys.json"),
+            ..Default::default()
+        }
+    }
+
+    pub fn load(profile: &str) -> Result<Self> {
+        let file = ProfileFile::new(profile, "surveys.json");
+        Ok(SurveyStore {
+            file: file.clone(),
+            ..file.load()?
+        })
+    }
+
+    pub fn save(&self) -> Result<()> {
+        self.file.save(self)
+    }
+
+    pub fn add(&mut self, surveys: Vec<Survey>) {
+        for survey in surveys {
+            self.surveys
+                .entry(survey.symbol.clone())
+                .or_default()
+                .push(survey);
+        }
+    }
+
+    pub fn find(&self, signature: &str) -> Option<&Survey> {
+        self.surveys
+            .values()
+            .flatten()
+            .find(|survey| survey.signature == signature)
+    }
+
+    /// Drops every survey expired by `now`, returning how many were removed
+    pub fn prune(&mut self, now: DateTime<Utc>) -> usize {
+        let before: usize = self.surveys.values().map(Vec::len).sum();
+        for surveys in self.surveys.values_mut() {
+            surveys.retain(|survey| !is_expired(survey, now));
+        }
+        self.surveys.retain(|_, surveys| !surveys.is_empty());
+        before - self.surveys.values().map(Vec::len).sum::<usize>()
+    }
+
+    /// The survey at a waypoint unexpired at `now` with the highest expected yield of a good
+    pub fn best(
+        &self,
+        waypoint: &str,
+        good: &TradeSymbol,
+        now: DateTime<Utc>,
+    ) -> Option<(&Survey, f64)> {
+        self.surveys
+            .get(waypoint)?
+            .iter()
+            .filter(|survey| !is_expired(survey, now))
+            .map(|survey| (survey, yield_score(survey, good)))
+            .filter(|(_, score)| *score > 0.0)
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+    }
+}
+
+/// Whether a survey has expired by `now`, one with an unreadable expiration counts as expired
+pub fn is_expired(survey: &Survey, now: DateTime<Utc>) -> bool {
+    DateTime::parse_from_rfc3339(&survey.expiration)
+        .map(|expiration| expiration.with_timezone(&Utc) <= now)
+        .unwrap_or(true)
+}
+
+/// Share of a survey's deposits that are the given good
+pub fn deposit_share(survey: &Survey, good: &TradeSymbol) -> f64 {
+    if survey.deposits.is_empty() {
+        return 0.0;
+    }
+    let matching = survey
+        .deposits
+        .iter()
+        .filter(|deposit| &deposit.symbol == good)
+        .count();
+    matching as f64 / survey.deposits.len() as f64
+}
+
+/// Deposit share of a good weighted by the survey size, larger surveys last longer
+fn yield_score(survey: &Survey, good: &TradeSymbol) -> f64 {
+    let size = match survey.size {
+        SurveySize::Small => 1.0,
+        SurveySize::Moderate => 2.0,
+        SurveySize::Large => 3.0,
+    };
+    deposit_share(survey, good) * size
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn survey(signature: &str, deposits: &[&str], size: &str, expiration: &str) -> Survey {
+        let deposits: Vec<_> = deposits
+            .iter()
+            .map(|symbol| serde_json::json!({ "symbol": symbol }))
+            .collect();
+        serde_json::from_value(serde_json::json!({
+            "signature": signature,
+            "symbol": "X1-TEST-B2",
+            "deposits": deposits,
+            "expiration": expiration,
+            "size": size,
+        }))
+        .unwrap()
+    }
+
+    fn store() -> SurveyStore {
+        let mut store = SurveyStore::default();
+        store.add(vec![
+            survey(
+                "SMALL-RICH",
+                &["IRON_ORE", "IRON_ORE", "QUARTZ_SAND"],
+                "SMALL",
+                "2024-01-01T12:00:00Z",
+            ),
+            survey(
+                "LARGE-POOR",
+                &["IRON_ORE", "QUARTZ_SAND", "QUARTZ_SAND"],
+                "LARGE",
+                "2024-01-01T12:00:00Z",
+            ),
+            survey(
+                "LARGE-RICH-EXPIRED",
+                &["IRON_ORE", "IRON_ORE", "IRON_ORE"],
+                "LARGE",
+                "2024-01-01T10:00:00Z",
+            ),
+            survey("UNREADABLE", &["IRON_ORE"], "LARGE", "soon"),
+        ]);
+        store
+    }
+
+    fn at(time: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(time)
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    #[test]
+    fn best_weighs_the_deposit_share_by_size_and_skips_expired_surveys() {
+        let store = store();
+        let iron: TradeSymbol = serde_json::from_str("\"IRON_ORE\"").unwrap();
+
+        let (survey, score) = store
+            .best("X1-TEST-B2", &iron, at("2024-01-01T11:00:00Z"))
+            .unwrap();
+        assert_eq!(survey.signature, "LARGE-POOR");
+        assert!((score - 1.0).abs() < 1e-9);
+
+        let (survey, _) = store
+            .best("X1-TEST-B2", &iron, at("2024-01-01T09:00:00Z"))
+            .unwrap();
+        assert_eq!(survey.signature, "LARGE-RICH-EXPIRED");
+
+        assert!(store
+            .best("X1-TEST-B2", &iron, at("2024-01-01T12:00:00Z"))
+            .is_none());
+        assert!(store
+            .best("X1-TEST-C3", &iron, at("2024-01-01T09:00:00Z"))
+            .is_none());
+    }
+
+    #[test]
+    fn prune_drops_expired_and_unreadable_surveys() {
+        let mut store = store();
+
+        assert_eq!(store.prune(at("2024-01-01T11:00:00Z")), 2);
+        let mut left: Vec<_> = store.surveys["X1-TEST-B2"]
+            .iter()
+            .map(|survey| survey.signature.as_str())
+            .collect();
+        left.sort();
+        assert_eq!(left, vec!["LARGE-POOR", "SMALL-RICH"]);
+
+        assert_eq!(store.prune(at("2024-01-01T12:00:00Z")), 2);
+        assert!(store.surveys.is_empty());
+    }
+}
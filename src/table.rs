@@ -0,0 +1,253 @@
+use clap::Args;
+use terminal_size::{terminal_size, Width};
+
+use crate::output::OutputFormat;
+
+/// Width used when stdout is not a terminal
+const DEFAULT_WIDTH: usize = 120;
+/// Columns are never shrunk below this many characters to fit the terminal
+const MIN_COLUMN_WIDTH: usize = 8;
+const SEPARATOR: &str = "  ";
+
+#[derive(Args, Debug, Clone, Default)]
+pub struct TableArgs {
+    /// Column to sort rows by
+    #[arg(long)]
+    pub sort: Option<String>,
+    /// Comma separated list of columns to show
+    #[arg(long, value_delimiter = ',')]
+    pub columns: Option<Vec<String>>,
+}
+
+impl TableArgs {
+    /// Sorting and column selection work on the text table, so they are rejected for
+    /// structured output rather than silently ignored
+    pub fn check(&self, output: OutputFormat) -> anyhow::Result<()> {
+        if output != OutputFormat::Text && (self.sort.is_some() || self.columns.is_some()) {
+            anyhow::bail!("--sort and --columns only apply to text output");
+        }
+        Ok(())
+    }
+}
+
+/// Rows of text cells printed as aligned columns that fit the terminal width
+pub struct Table {
+    headers: Vec<String>,
+    rows: Vec<Vec<String>>,
+}
+
+impl Table {
+    pub fn new(headers: &[&str]) -> Self {
+        Table {
+            headers: headers.iter().map(|h| h.to_uppercase()).collect(),
+            rows: Vec::new(),
+        }
+    }
+
+    pub fn row(&mut self, cells: Vec<String>) {
+        self.rows.push(cells);
+    }
+
+    /// Formats the table after applying the sort and column selection
+    pub fn format(&self, args: &TableArgs) -> anyhow::Result<String> {
+        let selected = match &args.columns {
+            Some(columns) => columns
+                .iter()
+                .map(|column| self.column_index(column))
+                .collect::<anyhow::Result<Vec<_>>>()?,
+            None => (0..self.headers.len()).collect(),
+        };
+
+        let mut rows: Vec<&Vec<String>> = self.rows.iter().collect();
+        if let Some(sort) = &args.sort {
+            let index = self.column_index(sort)?;
+            rows.sort_by(
+                |a, b| match (a[index].parse::<f64>(), b[index].parse::<f64>()) {
+                    (Ok(x), Ok(y)) => x.total_cmp(&y),
+                    _ => a[index].cmp(&b[index]),
+                },
+            );
+        }
+
+        let headers: Vec<&str> = selected.iter().map(|&i| self.headers[i].as_str()).collect();
+        let rows: Vec<Vec<&str>> = rows
+            .iter()
+            .map(|row| selected.iter().map(|&i| row[i].as_str()).collect())
+            .collect();
+        let widths = fit_widths(&headers, &rows, terminal_width());
+
+        let mut out = String::new();
+        push_line(&mut out, &headers, &widths);
+        let dashes: Vec<String> = widths.iter().map(|&w| "-".repeat(w)).collect();
+        out.push_str(dashes.join(SEPARATOR).trim_end());
+        out.push('\n');
+        for row in &rows {
+            push_line(&mut out, row, &widths);
+        }
+        Ok(out)
+    }
+
+    fn column_index(&self, name: &str) -> anyhow::Result<usize> {
+        self.headers
+            .iter()
+            .position(|h| h.eq_ignore_ascii_case(name))
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "Unknown column '{}', expected one of: {}",
+                    name,
+                    self.headers.join(", ").to_lowercase()
+                )
+            })
+    }
+}
+
+fn terminal_width() -> usize {
+    terminal_size()
+        .map(|(Width(width), _)| width as usize)
+        .unwrap_or(DEFAULT_WIDTH)
+}
+
+/// Sizes each column to its widest cell, then shrinks the widest columns until the
+/// table fits in the available width
+fn fit_widths(headers: &[&str], rows: &[Vec<&str>], available: usize) -> Vec<usize> {
+    let mut widths: Vec<usize> = headers.iter().map(|h| h.len()).collect();
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    let separators = SEPARATOR.len() * widths.len().saturating_sub(1);
+    while widths.iter().sum::<usize>() + separators > available {
+        let (widest, width) = widths
+            .iter()
+            .enumerate()
+            .max_by_key(|(_, width)| **width)
+            .map(|(i, w)| (i, *w))
+            .unwrap_or((0, 0));
+        if width <= MIN_COLUMN_WIDTH.max(headers[widest].len()) {
+            break;
+        }
+        widths[widest] -= 1;
+    }
+    widths
+}
+
+/// Appends one row, wrapping cells that are wider than their column onto extra lines
+fn push_line(out: &mut String, cells: &[&str], widths: &[usize]) {
+    let wrapped: Vec<Vec<String>> = cells
+        .iter()
+        .zip(widths)
+        .map(|(cell, &width)| wrap(cell, width))
+        .collect();
+    let height = wrapped.iter().map(Vec::len).max().unwrap_or(1);
+    for line in 0..height {
+        let parts: Vec<String> = wrapped
+            .iter()
+            .zip(widths)
+            .map(|(lines, &width)| {
+                format!(
+                    "{:<width$}",
+                    lines.get(line).map(String::as_str).unwrap_or(""),
+                    width = width
+                )
+            })
+            .collect();
+        out.push_str(parts.join(SEPARATOR).trim_end());
+        out.push('\n');
+    }
+}
+
+/// Greedily wraps text on spaces, breaking words that are longer than the width
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut current = String::new();
+    for word in text.split(' ') {
+        let mut word: Vec<char> = word.chars().collect();
+        while word.len() > width {
+            if !current.is_empty() {
+                lines.push(std::mem::take(&mut current));
+            }
+            lines.push(word.drain(..width).collect());
+        }
+        let word: String = word.into_iter().collect();
+        if current.is_empty() {
+            current = word;
+        } else if current.chars().count() + 1 + word.chars().count() <= width {
+            current.push(' ');
+            current.push_str(&word);
+        } else {
+            lines.push(std::mem::replace(&mut current, word));
+        }
+    }
+    if !current.is_empty() || lines.is_empty() {
+        lines.push(current);
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table() -> Table {
+        let mut table = Table::new(&["symbol", "price"]);
+        table.row(vec![String::from("IRON_ORE"), String::from("100")]);
+        table.row(vec![String::from("COPPER_ORE"), String::from("20")]);
+        table.row(vec![String::from("ALUMINUM_ORE"), String::from("3")]);
+        table
+    }
+
+    #[test]
+    fn columns_are_sized_to_their_widest_cell() {
+        let rows = vec![vec!["IRON_ORE", "1"], vec!["ICE", "12345"]];
+        assert_eq!(fit_widths(&["SYMBOL", "PRICE"], &rows, 120), vec![8, 5]);
+    }
+
+    #[test]
+    fn the_widest_column_is_shrunk_to_fit() {
+        let long = "a".repeat(30);
+        let rows = vec![vec![long.as_str(), "short"]];
+        assert_eq!(fit_widths(&["NAME", "OTHER"], &rows, 20), vec![13, 5]);
+        // Columns never shrink below the minimum width, even if the table overflows
+        assert_eq!(fit_widths(&["NAME", "OTHER"], &rows, 5), vec![8, 5]);
+    }
+
+    #[test]
+    fn cells_wrap_on_spaces_and_long_words_are_split() {
+        assert_eq!(wrap("a b c", 3), vec!["a b", "c"]);
+        assert_eq!(wrap("abcdefgh ij", 3), vec!["abc", "def", "gh", "ij"]);
+        assert_eq!(wrap("", 3), vec![""]);
+    }
+
+    #[test]
+    fn wrapped_cells_continue_on_extra_lines() {
+        let mut out = String::new();
+        push_line(&mut out, &["one two", "x"], &[3, 1]);
+        assert_eq!(out, "one  x\ntwo\n");
+    }
+
+    #[test]
+    fn rows_sort_numerically_and_columns_are_selected() {
+        let args = TableArgs {
+            sort: Some(String::from("price")),
+            columns: Some(vec![String::from("Symbol")]),
+        };
+        assert_eq!(
+            table().format(&args).unwrap(),
+            "SYMBOL\n------------\nALUMINUM_ORE\nCOPPER_ORE\nIRON_ORE\n"
+        );
+    }
+
+    #[test]
+    fn unknown_columns_are_errors() {
+        let args = TableArgs {
+            sort: Some(String::from("volume")),
+            columns: None,
+        };
+        assert_eq!(
+            table().format(&args).unwrap_err().to_string(),
+            "Unknown column 'volume', expected one of: symbol, price"
+        );
+    }
+}
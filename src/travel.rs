@@ -0,0 +1,83 @@
+use space_traders_sdk::ship::ShipFlightMode;
+
+/// Straight line distance between two points in a system
+pub fn distance(from: (i32, i32), to: (i32, i32)) -> f64 {
+    ((to.0 - from.0) as f64).hypot((to.1 - from.1) as f64)
+}
+
+/// Fuel a navigation over the given distance costs in the given flight mode
+pub fn fuel_required(distance: f64, mode: &ShipFlightMode) -> u32 {
+    let distance = distance.round() as u32;
+    match mode {
+        ShipFlightMode::Drift => 1,
+        ShipFlightMode::Burn => (distance * 2).max(2),
+        ShipFlightMode::Cruise | ShipFlightMode::Stealth => distance.max(1),
+    }
+}
+
+/// Seconds a navigation over the given distance takes for an engine speed and flight mode
+pub fn travel_seconds(distance: f64, engine_speed: u32, mode: &ShipFlightMode) -> u64 {
+    let multiplier = match mode {
+        ShipFlightMode::Cruise => 25.0,
+        ShipFlightMode::Burn => 12.5,
+        ShipFlightMode::Drift => 250.0,
+        ShipFlightMode::Stealth => 30.0,
+    };
+    (distance.max(1.0).round() * (multiplier / engine_speed.max(1) as f64) + 15.0).round() as u64
+}
+
+pub fn format_duration(seconds: u64) -> String {
+    format!(
+        "{}h {:02}m {:02}s",
+        seconds / 3600,
+        (seconds % 3600) / 60,
+        seconds % 60
+    )
+}
+
+/// System symbol a waypoint belongs to, e.g. `X1-AB12` for `X1-AB12-C3`
+pub fn system_symbol(waypoint: &str) -> &str {
+    waypoint
+        .rsplit_once('-')
+        .map_or(waypoint, |(system, _)| system)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn distances_are_straight_lines() {
+        assert_eq!(distance((0, 0), (3, 4)), 5.0);
+        assert_eq!(distance((-2, 1), (-2, -4)), 5.0);
+    }
+
+    #[test]
+    fn fuel_depends_on_the_flight_mode() {
+        assert_eq!(fuel_required(5.0, &ShipFlightMode::Cruise), 5);
+        assert_eq!(fuel_required(7.6, &ShipFlightMode::Stealth), 8);
+        assert_eq!(fuel_required(5.0, &ShipFlightMode::Burn), 10);
+        assert_eq!(fuel_required(5.0, &ShipFlightMode::Drift), 1);
+        // Even a zero length hop costs fuel
+        assert_eq!(fuel_required(0.0, &ShipFlightMode::Cruise), 1);
+        assert_eq!(fuel_required(0.4, &ShipFlightMode::Burn), 2);
+    }
+
+    #[test]
+    fn travel_time_scales_with_distance_and_engine_speed() {
+        assert_eq!(travel_seconds(10.0, 10, &ShipFlightMode::Cruise), 40);
+        assert_eq!(travel_seconds(10.0, 30, &ShipFlightMode::Burn), 19);
+        assert_eq!(travel_seconds(10.0, 10, &ShipFlightMode::Drift), 265);
+        assert_eq!(travel_seconds(0.0, 10, &ShipFlightMode::Cruise), 18);
+        // A ship without a working engine is treated as speed 1 rather than dividing by 0
+        assert_eq!(travel_seconds(1.0, 0, &ShipFlightMode::Cruise), 40);
+    }
+
+    #[test]
+    fn durations_and_system_symbols_are_formatted() {
+        assert_eq!(format_duration(3725), "1h 02m 05s");
+        assert_eq!(format_duration(59), "0h 00m 59s");
+        assert_eq!(system_symbol("X1-AB12-C3"), "X1-AB12");
+        assert_eq!(system_symbol("X1"), "X1");
+    }
+}
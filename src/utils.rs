@@ -1,4 +1,22 @@
+use serde::Serialize;
 use serde_json::Value;
+use space_traders_sdk::market::TradeSymbol;
+
+/// The serialized name of an enum value without JSON quotes, e.g. `MARKETPLACE`
+pub fn enum_name<T: Serialize>(value: &T) -> String {
+    match serde_json::to_value(value) {
+        Ok(Value::String(name)) => name,
+        Ok(other) => other.to_string(),
+        Err(_) => String::new(),
+    }
+}
+
+/// Parses a trade symbol such as `iron_ore` or `IRON_ORE` from a command line argument
+pub fn parse_trade_symbol(input: &str) -> Result<TradeSymbol, String> {
+    let symbol = input.trim().to_uppercase().replace('-', "_");
+    serde_json::from_value(Value::String(symbol))
+        .map_err(|_| format!("unknown trade symbol '{}'", input))
+}
 
 pub fn _print_json_pretty(input: &str) {
     match serde_json::from_str::<Value>(input) {
@@ -11,7 +29,3 @@ pub fn _print_json_pretty(input: &str) {
         Err(_) => println!("{}", input),
     }
 }
-
-pub fn _print_json_value(value: &Value) {
-    println!("{}", serde_json::to_string_pretty(value).unwrap());
-}
@@ -0,0 +1,169 @@
+use argon2::Argon2;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chacha20poly1305::{
+    aead::{rand_core::RngCore, Aead, AeadCore, KeyInit, OsRng},
+    Key, XChaCha20Poly1305, XNonce,
+};
+use rpassword::prompt_password;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{Error, ErrorKind, Result, Write};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use crate::config::config_path;
+
+/// Supplies the vault passphrase without prompting, for scripts and tests
+pub const PASSPHRASE_ENV: &str = "SPACE_TRADERS_CLI_VAULT_PASSPHRASE";
+
+/// Passphrase entered this session, so the vault is only unlocked once
+static PASSPHRASE: OnceLock<String> = OnceLock::new();
+
+/// Tokens belonging to one config profile
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ProfileTokens {
+    pub account_token: String,
+    /// Agent tokens keyed by callsign
+    pub agents: BTreeMap<String, String>,
+}
+
+impl ProfileTokens {
+    pub fn is_empty(&self) -> bool {
+        self.account_token.is_empty() && self.agents.values().all(String::is_empty)
+    }
+}
+
+/// Every token the CLI knows, kept encrypted on disk
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Vault {
+    pub profiles: BTreeMap<String, ProfileTokens>,
+}
+
+/// On disk form of the vault, the key is derived from the passphrase and salt
+#[derive(Serialize, Deserialize)]
+struct SealedVault {
+    salt: String,
+    nonce: String,
+    ciphertext: String,
+}
+
+impl Vault {
+    pub fn exists() -> bool {
+        vault_path().exists()
+    }
+
+    /// Decrypts the vault, an empty vault is returned when none has been written yet
+    pub fn load() -> Result<Self> {
+        let path = vault_path();
+        if !path.exists() {
+            return Ok(Vault::default());
+        }
+        let sealed: SealedVault = serde_json::from_str(&fs::read_to_string(path)?)?;
+        let salt = decode(&sealed.salt)?;
+        let nonce = decode(&sealed.nonce)?;
+        let ciphertext = decode(&sealed.ciphertext)?;
+
+        let cipher = cipher(&passphrase(false)?, &salt)?;
+        let plaintext = cipher
+            .decrypt(XNonce::from_slice(&nonce), ciphertext.as_ref())
+            .map_err(|_| {
+                Error::new(
+                    ErrorKind::PermissionDenied,
+                    "Failed to unlock the token vault, is the passphrase correct?",
+                )
+            })?;
+        Ok(serde_json::from_slice(&plaintext)?)
+    }
+
+    /// Encrypts the vault with a fresh salt and nonce, readable only by the current user
+    pub fn save(&self) -> Result<()> {
+        let passphrase = passphrase(!Vault::exists())?;
+        let mut salt = [0u8; 16];
+        OsRng.fill_bytes(&mut salt);
+        let cipher = cipher(&passphrase, &salt)?;
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let plaintext = serde_json::to_vec(self)?;
+        let ciphertext = cipher
+            .encrypt(&nonce, plaintext.as_ref())
+            .map_err(|_| Error::other("Failed to encrypt the token vault"))?;
+
+        let sealed = SealedVault {
+            salt: STANDARD.encode(salt),
+            nonce: STANDARD.encode(nonce),
+            ciphertext: STANDARD.encode(ciphertext),
+        };
+        write_private(&vault_path(), &serde_json::to_string_pretty(&sealed)?)
+    }
+}
+
+/// Asks for the passphrase once per session, twice when a new vault is being created
+fn passphrase(creating: bool) -> Result<String> {
+    if let Some(passphrase) = PASSPHRASE.get() {
+        return Ok(passphrase.clone());
+    }
+
+    let passphrase = match std::env::var(PASSPHRASE_ENV) {
+        Ok(passphrase) => passphrase,
+        Err(_) if creating => {
+            eprintln!("Tokens are stored encrypted, choose a passphrase for the token vault");
+            let passphrase = prompt_password("Passphrase: ")?;
+            if prompt_password("Confirm passphrase: ")? != passphrase {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    "Passphrases did not match",
+                ));
+            }
+            passphrase
+        }
+        Err(_) => prompt_password("Vault passphrase: ")?,
+    };
+    Ok(PASSPHRASE.get_or_init(|| passphrase).clone())
+}
+
+fn cipher(passphrase: &str, salt: &[u8]) -> Result<XChaCha20Poly1305> {
+    let mut key = [0u8; 32];
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| Error::other(format!("Failed to derive vault key: {}", e)))?;
+    Ok(XChaCha20Poly1305::new(Key::from_slice(&key)))
+}
+
+fn decode(value: &str) -> Result<Vec<u8>> {
+    STANDARD
+        .decode(value)
+        .map_err(|e| Error::new(ErrorKind::InvalidData, e))
+}
+
+/// Writes a file readable only by the current user. The contents go to a temporary file
+/// next to it that replaces it once synced, so an interrupted write leaves the old file.
+fn write_private(path: &Path, contents: &str) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut temp_path = path.as_os_str().to_owned();
+    temp_path.push(".tmp");
+    let temp_path = PathBuf::from(temp_path);
+
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options.open(&temp_path)?;
+    // The mode only applies to new files, a leftover temporary file keeps its own
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        file.set_permissions(fs::Permissions::from_mode(0o600))?;
+    }
+    file.write_all(contents.as_bytes())?;
+    file.sync_all()?;
+    fs::rename(&temp_path, path)
+}
+
+fn vault_path() -> PathBuf {
+    config_path().with_file_name("vault.json")
+}
@@ -0,0 +1,515 @@
+mod common;
+
+use std::sync::{
+    atomic::{AtomicU32, Ordering},
+    Arc,
+};
+use std::time::Duration;
+
+use common::{application, config, requests_to, run, start_server, CALLSIGN};
+use space_traders_cli::repl::{run_script, Session};
+use wiremock::{
+    matchers::{method, path},
+    Mock, ResponseTemplate,
+};
+
+#[tokio::test]
+async fn agent_info_loads_the_agent_on_first_use() {
+    let server = start_server().await;
+    let mut application = application(config(&server));
+    assert!(application.agents.is_empty());
+
+    run(&mut application, "agent info --callsign MOCK_AGENT")
+        .await
+        .unwrap();
+
+    let agent = application.agents.get(CALLSIGN).expect("Agent not loaded");
+    assert_eq!(agent.data.credits, 175000);
+    assert_eq!(agent.ships().count(), 1);
+    assert_eq!(agent.contracts().count(), 1);
+}
+
+#[tokio::test]
+async fn list_agents_reports_agents_with_rejected_tokens() {
+    let server = start_server().await;
+    let mut config = config(&server);
+    config.agents.push(space_traders_cli::config::Agent {
+        id: String::from("STALE_AGENT"),
+        token: String::from("expired-token"),
+        faction: None,
+        reset_date: None,
+    });
+    let mut application = application(config);
+
+    run(&mut application, "agent list-agents").await.unwrap();
+
+    assert!(application.agents.contains_key(CALLSIGN));
+    assert!(application.unavailable_agents.contains_key("STALE_AGENT"));
+}
+
+#[tokio::test]
+async fn contract_accept_posts_to_the_contract() {
+    let server = start_server().await;
+    let mut application = application(config(&server));
+
+    run(&mut application, "contract list --callsign MOCK_AGENT")
+        .await
+        .unwrap();
+    run(
+        &mut application,
+        "contract accept --callsign MOCK_AGENT --id mock-contract",
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(
+        requests_to(&server, "POST", "/my/contracts/mock-contract/accept").await,
+        1
+    );
+}
+
+#[tokio::test]
+async fn unknown_contract_ids_are_errors() {
+    let server = start_server().await;
+    let mut application = application(config(&server));
+
+    for line in [
+        "contract info -c MOCK_AGENT --id missing",
+        "contract accept -c MOCK_AGENT --id missing",
+        "contract deliver -c MOCK_AGENT --id missing -s MOCK_AGENT-1 -g IRON_ORE -u 1",
+        "contract fulfill -c MOCK_AGENT --id missing",
+    ] {
+        let error = run(&mut application, line).await.unwrap_err();
+        assert_eq!(error.to_string(), "No known contract with that ID");
+    }
+}
+
+#[tokio::test]
+async fn ship_orbit_uses_the_agent_ship() {
+    let server = start_server().await;
+    let mut application = application(config(&server));
+
+    run(
+        &mut application,
+        "ship orbit --callsign MOCK_AGENT --ship MOCK_AGENT-1",
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(
+        requests_to(&server, "POST", "/my/ships/MOCK_AGENT-1/orbit").await,
+        1
+    );
+}
+
+fn iron_ore_cargo(units: u32) -> serde_json::Value {
+    let inventory = if units == 0 {
+        serde_json::json!([])
+    } else {
+        serde_json::json!([{
+            "symbol": "IRON_ORE",
+            "name": "Iron Ore",
+            "description": "Unrefined iron.",
+            "units": units
+        }])
+    };
+    serde_json::json!({ "capacity": 200, "units": units, "inventory": inventory })
+}
+
+#[tokio::test]
+async fn sales_larger_than_the_trade_volume_are_split_into_batches() {
+    let server = start_server().await;
+    let held = Arc::new(AtomicU32::new(150));
+    Mock::given(method("POST"))
+        .and(path("/my/ships/MOCK_AGENT-1/sell"))
+        .respond_with(move |request: &wiremock::Request| {
+            let body: serde_json::Value = request.body_json().unwrap();
+            let units = body["units"].as_u64().unwrap() as u32;
+            let left = held.fetch_sub(units, Ordering::SeqCst) - units;
+            ResponseTemplate::new(201).set_body_json(serde_json::json!({
+                "data": {
+                    "agent": {
+                        "accountId": "mock-account",
+                        "symbol": CALLSIGN,
+                        "headquarters": "X1-TEST-A1",
+                        "credits": 175000 + (150 - left) * 60,
+                        "startingFaction": "COSMIC",
+                        "shipCount": 1
+                    },
+                    "cargo": iron_ore_cargo(left),
+                    "transaction": {
+                        "waypointSymbol": "X1-TEST-A1",
+                        "shipSymbol": "MOCK_AGENT-1",
+                        "tradeSymbol": "IRON_ORE",
+                        "type": "SELL",
+                        "units": units,
+                        "pricePerUnit": 60,
+                        "totalPrice": units * 60,
+                        "timestamp": "2024-01-01T00:00:00.000Z"
+                    }
+                }
+            }))
+        })
+        .mount(&server)
+        .await;
+    let mut application = application(config(&server));
+    assert!(application.load_agent(CALLSIGN).await);
+    application
+        .agents
+        .get_mut(CALLSIGN)
+        .unwrap()
+        .edit_ship("MOCK_AGENT-1")
+        .data
+        .cargo = serde_json::from_value(iron_ore_cargo(150)).unwrap();
+
+    assert!(run(
+        &mut application,
+        "ship sell -c MOCK_AGENT -s MOCK_AGENT-1 -g IRON_ORE --units 0",
+    )
+    .await
+    .is_err());
+    run(
+        &mut application,
+        "ship sell -c MOCK_AGENT -s MOCK_AGENT-1 -g IRON_ORE --all",
+    )
+    .await
+    .unwrap();
+
+    let batches: Vec<_> = server
+        .received_requests()
+        .await
+        .unwrap()
+        .iter()
+        .filter(|request| request.url.path() == "/my/ships/MOCK_AGENT-1/sell")
+        .map(|request| request.body_json::<serde_json::Value>().unwrap()["units"].clone())
+        .collect();
+    assert_eq!(batches, vec![60, 60, 30]);
+    let agent = application.agents.get_mut(CALLSIGN).unwrap();
+    assert_eq!(agent.data.credits, 175000 + 150 * 60);
+    assert_eq!(agent.edit_ship("MOCK_AGENT-1").data.cargo.units, 0);
+}
+
+#[tokio::test]
+async fn list_waypoints_uses_the_public_client() {
+    let server = start_server().await;
+    let mut application = application(config(&server));
+
+    run(&mut application, "system list-waypoints --system X1-TEST")
+        .await
+        .unwrap();
+
+    assert_eq!(
+        requests_to(&server, "GET", "/systems/X1-TEST/waypoints").await,
+        1
+    );
+    assert!(application.agents.is_empty());
+}
+
+#[tokio::test]
+async fn market_view_updates_the_market_cache() {
+    let server = start_server().await;
+    let mut application = application(config(&server));
+
+    run(
+        &mut application,
+        "system waypoint --system X1-TEST --waypoint X1-TEST-A1 market --callsign MOCK_AGENT",
+    )
+    .await
+    .unwrap();
+
+    let market = application
+        .markets
+        .markets
+        .get("X1-TEST-A1")
+        .expect("Market not cached");
+    assert_eq!(market.trade_goods.as_ref().map(Vec::len), Some(2));
+}
+
+#[tokio::test]
+async fn shipyard_view_fetches_the_shipyard() {
+    let server = start_server().await;
+    let mut application = application(config(&server));
+
+    run(
+        &mut application,
+        "system waypoint --system X1-TEST --waypoint X1-TEST-A1 shipyard",
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(
+        requests_to(
+            &server,
+            "GET",
+            "/systems/X1-TEST/waypoints/X1-TEST-A1/shipyard"
+        )
+        .await,
+        1
+    );
+}
+
+#[tokio::test]
+async fn server_reset_marks_stored_agents_stale() {
+    let server = start_server().await;
+    let mut config = config(&server);
+    // Stored before agents kept their own reset date
+    config.agents[0].reset_date = None;
+    config.reset_date = Some(String::from("2023-12-17"));
+    let mut application = application(config);
+
+    application.check_server_reset().await.unwrap();
+
+    assert_eq!(
+        application.server_reset_date.as_deref(),
+        Some(common::RESET_DATE)
+    );
+    assert!(application.unavailable_agents.contains_key(CALLSIGN));
+    assert!(!application.load_agent(CALLSIGN).await);
+}
+
+#[tokio::test]
+async fn server_reset_only_marks_agents_registered_before_it() {
+    let server = start_server().await;
+    let mut config = config(&server);
+    config.agents.push(space_traders_cli::config::Agent {
+        id: String::from("OLD_AGENT"),
+        token: String::from("old-token"),
+        faction: None,
+        reset_date: Some(String::from("2023-12-17")),
+    });
+    let mut application = application(config);
+
+    application.check_server_reset().await.unwrap();
+
+    assert!(application.unavailable_agents.contains_key("OLD_AGENT"));
+    assert!(!application.unavailable_agents.contains_key(CALLSIGN));
+    assert!(application.load_agent(CALLSIGN).await);
+}
+
+#[tokio::test]
+async fn rate_limited_requests_are_retried() {
+    let server = start_server().await;
+    Mock::given(method("GET"))
+        .and(path("/my/agent"))
+        .respond_with(ResponseTemplate::new(429).set_body_json(serde_json::json!({
+            "error": {
+                "message": "You have reached your API limit.",
+                "code": 429,
+                "data": { "type": "IP_LIMIT", "retryAfter": 0.2 }
+            }
+        })))
+        .up_to_n_times(1)
+        .with_priority(1)
+        .mount(&server)
+        .await;
+    let mut application = application(config(&server));
+
+    let started = std::time::Instant::now();
+    assert!(application.load_agent(CALLSIGN).await);
+    assert!(started.elapsed() >= Duration::from_millis(200));
+    assert_eq!(requests_to(&server, "GET", "/my/agent").await, 2);
+}
+
+#[tokio::test]
+async fn rate_limited_ship_actions_are_retried() {
+    let server = start_server().await;
+    Mock::given(method("POST"))
+        .and(path("/my/ships/MOCK_AGENT-1/orbit"))
+        .respond_with(ResponseTemplate::new(429).set_body_json(serde_json::json!({
+            "error": {
+                "message": "You have reached your API limit.",
+                "code": 429,
+                "data": { "type": "IP_LIMIT", "retryAfter": 0.2 }
+            }
+        })))
+        .up_to_n_times(1)
+        .with_priority(1)
+        .mount(&server)
+        .await;
+    let mut application = application(config(&server));
+
+    run(
+        &mut application,
+        "ship orbit --callsign MOCK_AGENT --ship MOCK_AGENT-1",
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(
+        requests_to(&server, "POST", "/my/ships/MOCK_AGENT-1/orbit").await,
+        2
+    );
+}
+
+#[tokio::test]
+async fn scripts_skip_comments_and_stop_on_error() {
+    let server = start_server().await;
+    let mut application = application(config(&server));
+    let script = tempfile::NamedTempFile::new().unwrap();
+    std::fs::write(
+        script.path(),
+        "# morning routine\n\
+         set -x\n\
+         use MOCK_AGENT\n\
+         ship orbit --ship MOCK_AGENT-1\n\
+         set -e\n\
+         ship orbt\n\
+         contract accept --id mock-contract\n",
+    )
+    .unwrap();
+
+    let result = run(
+        &mut application,
+        &format!("run {}", script.path().display()),
+    )
+    .await;
+
+    assert!(result.is_err());
+    assert_eq!(
+        requests_to(&server, "POST", "/my/ships/MOCK_AGENT-1/orbit").await,
+        1
+    );
+    assert_eq!(
+        requests_to(&server, "POST", "/my/contracts/mock-contract/accept").await,
+        0
+    );
+}
+
+#[tokio::test]
+async fn use_ship_only_picks_configured_owners() {
+    let server = start_server().await;
+    let mut application = application(config(&server));
+    let script = tempfile::NamedTempFile::new().unwrap();
+    let mut session = Session::default();
+
+    std::fs::write(script.path(), "use ship OTHER_AGENT-1\n").unwrap();
+    run_script(&mut application, &mut session, script.path())
+        .await
+        .unwrap();
+    assert_eq!(session.callsign, None);
+    assert_eq!(session.ship, None);
+
+    std::fs::write(script.path(), "use ship MOCK_AGENT-1\n").unwrap();
+    run_script(&mut application, &mut session, script.path())
+        .await
+        .unwrap();
+    assert_eq!(session.callsign.as_deref(), Some(CALLSIGN));
+    assert_eq!(session.ship.as_deref(), Some("MOCK_AGENT-1"));
+}
+
+#[tokio::test]
+async fn scripts_stop_when_the_server_rejects_a_command() {
+    let server = start_server().await;
+    Mock::given(method("POST"))
+        .and(path("/my/ships/MOCK_AGENT-1/orbit"))
+        .respond_with(ResponseTemplate::new(400).set_body_json(serde_json::json!({
+            "error": { "message": "Ship is in transit.", "code": 4214 }
+        })))
+        .with_priority(1)
+        .mount(&server)
+        .await;
+    let mut application = application(config(&server));
+    let script = tempfile::NamedTempFile::new().unwrap();
+    std::fs::write(
+        script.path(),
+        "set -e\n\
+         ship orbit --callsign MOCK_AGENT --ship MOCK_AGENT-1\n\
+         contract accept --callsign MOCK_AGENT --id mock-contract\n",
+    )
+    .unwrap();
+
+    let result = run_script(&mut application, &mut Session::default(), script.path()).await;
+
+    assert!(result.is_err());
+    assert_eq!(
+        requests_to(&server, "POST", "/my/contracts/mock-contract/accept").await,
+        0
+    );
+}
+
+#[tokio::test]
+async fn scripts_cannot_source_themselves() {
+    let server = start_server().await;
+    let mut application = application(config(&server));
+    let script = tempfile::NamedTempFile::new().unwrap();
+    std::fs::write(
+        script.path(),
+        format!("set -e\nsource {}\n", script.path().display()),
+    )
+    .unwrap();
+
+    let result = run_script(&mut application, &mut Session::default(), script.path()).await;
+
+    assert!(result.is_err());
+}
+
+#[tokio::test]
+async fn scripts_run_from_a_script_share_its_session() {
+    let server = start_server().await;
+    let mut application = application(config(&server));
+    let inner = tempfile::NamedTempFile::new().unwrap();
+    std::fs::write(inner.path(), "ship orbit --ship MOCK_AGENT-1\n").unwrap();
+    let outer = tempfile::NamedTempFile::new().unwrap();
+    std::fs::write(
+        outer.path(),
+        format!("use MOCK_AGENT\nrun {}\n", inner.path().display()),
+    )
+    .unwrap();
+
+    run_script(&mut application, &mut Session::default(), outer.path())
+        .await
+        .unwrap();
+
+    assert_eq!(
+        requests_to(&server, "POST", "/my/ships/MOCK_AGENT-1/orbit").await,
+        1
+    );
+}
+
+#[tokio::test]
+async fn aliases_are_only_saved_when_every_definition_is_valid() {
+    let server = start_server().await;
+    let mut application = application(config(&server));
+    let script = tempfile::NamedTempFile::new().unwrap();
+    std::fs::write(
+        script.path(),
+        "set -e\nalias fleet='ship list -c MOCK_AGENT' ship=orbit\n",
+    )
+    .unwrap();
+
+    let result = run_script(&mut application, &mut Session::default(), script.path()).await;
+
+    assert!(result.is_err());
+    assert!(application.config.aliases.is_empty());
+}
+
+#[tokio::test]
+async fn failures_and_table_options_for_structured_output_are_errors() {
+    let server = start_server().await;
+    let mut application = application(config(&server));
+
+    assert!(run(&mut application, "agent info --callsign NOBODY")
+        .await
+        .is_err());
+    assert!(run(&mut application, "agent list-agents --sort credits")
+        .await
+        .is_err());
+}
+
+#[tokio::test]
+async fn refresh_keeps_the_stored_token_when_the_new_one_is_rejected() {
+    let server = start_server().await;
+    let mut application = application(config(&server));
+    assert!(application.load_agent(CALLSIGN).await);
+
+    let result = run(
+        &mut application,
+        "agent refresh --callsign MOCK_AGENT --token rejected-token",
+    )
+    .await;
+
+    assert!(result.is_err());
+    assert_eq!(application.config.agents[0].token, common::AGENT_TOKEN);
+    assert!(application.agents.contains_key(CALLSIGN));
+    assert!(!application.unavailable_agents.contains_key(CALLSIGN));
+}
@@ -0,0 +1,120 @@
+#![allow(dead_code)]
+
+use std::sync::OnceLock;
+
+use clap::Parser;
+use space_traders_cli::{
+    cli::{self, ReplCli},
+    config::{self, Agent, Config},
+    market_cache::MarketCache,
+    output::OutputFormat,
+    survey_store::SurveyStore,
+    vault, Application,
+};
+use tempfile::TempDir;
+use wiremock::{
+    matchers::{header, method, path},
+    Mock, MockServer, ResponseTemplate,
+};
+
+pub const CALLSIGN: &str = "MOCK_AGENT";
+pub const AGENT_TOKEN: &str = "mock-agent-token";
+pub const RESET_DATE: &str = "2024-01-01";
+
+/// Directory standing in for the user's config dir, shared by every test in the run
+static CONFIG_DIR: OnceLock<TempDir> = OnceLock::new();
+
+fn fixture(name: &str) -> serde_json::Value {
+    let path = format!(
+        "{}/tests/fixtures/{}.json",
+        env!("CARGO_MANIFEST_DIR"),
+        name
+    );
+    let contents = std::fs::read_to_string(&path).expect("Missing fixture");
+    serde_json::from_str(&contents).expect("Invalid fixture")
+}
+
+/// Starts a SpaceTraders stand-in serving the canned fixtures. Endpoints under `/my`
+/// only answer requests made with `AGENT_TOKEN`.
+pub async fn start_server() -> MockServer {
+    let server = MockServer::start().await;
+    let public = [
+        ("/", "status"),
+        ("/systems/X1-TEST/waypoints", "waypoints"),
+        ("/systems/X1-TEST/waypoints/X1-TEST-A1", "waypoint"),
+        ("/systems/X1-TEST/waypoints/X1-TEST-A1/market", "market"),
+        ("/systems/X1-TEST/waypoints/X1-TEST-A1/shipyard", "shipyard"),
+    ];
+    for (route, name) in public {
+        Mock::given(method("GET"))
+            .and(path(route))
+            .respond_with(ResponseTemplate::new(200).set_body_json(fixture(name)))
+            .mount(&server)
+            .await;
+    }
+
+    let authorized = [
+        ("GET", "/my/agent", "agent"),
+        ("GET", "/my/contracts", "contracts"),
+        ("GET", "/my/ships", "ships"),
+        (
+            "POST",
+            "/my/contracts/mock-contract/accept",
+            "accept_contract",
+        ),
+        ("POST", "/my/ships/MOCK_AGENT-1/orbit", "orbit"),
+    ];
+    for (verb, route, name) in authorized {
+        Mock::given(method(verb))
+            .and(path(route))
+            .and(header("Authorization", format!("Bearer {}", AGENT_TOKEN)))
+            .respond_with(ResponseTemplate::new(200).set_body_json(fixture(name)))
+            .mount(&server)
+            .await;
+    }
+    server
+}
+
+/// Config pointing at the mock server with the mock agent registered
+pub fn config(server: &MockServer) -> Config {
+    let mut config = Config::new("mock");
+    config.account_token = String::from("mock-account-token");
+    config.agents.push(Agent {
+        id: String::from(CALLSIGN),
+        token: String::from(AGENT_TOKEN),
+        faction: Some(String::from("COSMIC")),
+        reset_date: Some(String::from(RESET_DATE)),
+    });
+    config.reset_date = Some(String::from(RESET_DATE));
+    config.base_url = Some(server.uri());
+    config
+}
+
+/// Application with empty local stores, anything it saves goes to a temporary directory
+pub fn application(config: Config) -> Application {
+    let dir = CONFIG_DIR.get_or_init(|| TempDir::new().expect("Failed to create temp dir"));
+    std::env::set_var(config::CONFIG_DIR_ENV, dir.path());
+    std::env::set_var(vault::PASSPHRASE_ENV, "mock passphrase");
+    let surveys = SurveyStore::new(&config.profile);
+    let markets = MarketCache::new(&config.profile);
+    Application::new(config, surveys, markets, OutputFormat::Json)
+}
+
+/// Parses a command line the way the REPL does and runs it
+pub async fn run(application: &mut Application, line: &str) -> anyhow::Result<()> {
+    let args = std::iter::once("repl").chain(line.split_whitespace());
+    let parsed = ReplCli::try_parse_from(args)?;
+    let command = parsed.command.expect("No command given");
+    cli::handle_command(command, application).await
+}
+
+/// Number of requests the server received for a method and path
+pub async fn requests_to(server: &MockServer, verb: &str, route: &str) -> usize {
+    server
+        .received_requests()
+        .await
+        .unwrap_or_default()
+        .iter()
+        .filter(|request| request.method.as_str() == verb && request.url.path() == route)
+        .count()
+}
@@ -0,0 +1,116 @@
+use space_traders_cli::completion::{complete, highlight, hint, repl_command, CompletionValues};
+
+fn values() -> CompletionValues {
+    let mut values = CompletionValues::default();
+    values.callsigns.insert(String::from("ALPHA"));
+    values.callsigns.insert(String::from("BETA"));
+    values.ships.insert(
+        String::from("ALPHA"),
+        [String::from("ALPHA-1"), String::from("ALPHA-2")].into(),
+    );
+    values
+        .ships
+        .insert(String::from("BETA"), [String::from("BETA-1")].into());
+    values.waypoints.insert(String::from("X1-TEST-A1"));
+    values.aliases.insert(String::from("mkt"));
+    values
+}
+
+fn candidates(line: &str) -> (usize, Vec<String>) {
+    complete(&repl_command(), &values(), line)
+}
+
+#[test]
+fn completes_subcommands_after_typed_words() {
+    assert_eq!(candidates("sh"), (0, vec![String::from("ship")]));
+    let (start, names) = candidates("ship or");
+    assert_eq!(start, 5);
+    assert_eq!(names, vec![String::from("orbit")]);
+}
+
+#[test]
+fn completes_callsigns_and_ships_owned_by_them() {
+    let (start, names) = candidates("ship orbit --callsign ");
+    assert_eq!(start, 22);
+    assert_eq!(names, vec![String::from("ALPHA"), String::from("BETA")]);
+
+    let (_, names) = candidates("ship orbit -c ALPHA --ship AL");
+    assert_eq!(
+        names,
+        vec![String::from("ALPHA-1"), String::from("ALPHA-2")]
+    );
+}
+
+#[test]
+fn completes_value_enum_variants_and_flags() {
+    let (_, names) = candidates("ship flight-mode -c ALPHA -s ALPHA-1 d");
+    assert_eq!(names, vec![String::from("drift")]);
+
+    let (_, names) = candidates("ship navigate --t");
+    assert_eq!(names, vec![String::from("--to")]);
+}
+
+#[test]
+fn completes_session_context_targets() {
+    let (_, names) = candidates("use ");
+    assert_eq!(
+        names,
+        vec![
+            String::from("ALPHA"),
+            String::from("BETA"),
+            String::from("ship")
+        ]
+    );
+
+    let (_, names) = candidates("use ship B");
+    assert_eq!(names, vec![String::from("BETA-1")]);
+}
+
+#[test]
+fn hints_the_rest_of_a_word_or_the_required_arguments() {
+    let root = repl_command();
+    assert_eq!(
+        hint(&root, &values(), "contract neg", &[]),
+        Some(String::from("otiate"))
+    );
+    assert_eq!(
+        hint(&root, &values(), "ship orbit ", &[]),
+        Some(String::from("--callsign <CALLSIGN> --ship <SHIP>"))
+    );
+    assert_eq!(
+        hint(&root, &values(), "ship orbit ", &["callsign"]),
+        Some(String::from("--ship <SHIP>"))
+    );
+    assert_eq!(
+        hint(&root, &values(), "ship orbit -c ", &[]),
+        Some(String::from("<CALLSIGN>"))
+    );
+}
+
+#[test]
+fn highlights_unknown_words() {
+    let root = repl_command();
+    assert_eq!(
+        highlight(&root, &values(), "ship orbit --callsign A --shp"),
+        "\x1b[1;32mship\x1b[0m \x1b[1;32morbit\x1b[0m \x1b[36m--callsign\x1b[0m A \x1b[31m--shp\x1b[0m"
+    );
+}
+
+#[test]
+fn completes_aliases_with_commands() {
+    assert_eq!(candidates("mk"), (0, vec![String::from("mkt")]));
+    let (_, names) = candidates("unalias ");
+    assert_eq!(names, vec![String::from("mkt")]);
+}
+
+#[test]
+fn knows_the_help_flags_and_subcommands_clap_adds() {
+    let (_, names) = candidates("ship orbit --he");
+    assert_eq!(names, vec![String::from("--help")]);
+    let (_, names) = candidates("ship he");
+    assert_eq!(names, vec![String::from("help")]);
+    assert_eq!(
+        highlight(&repl_command(), &values(), "ship --help"),
+        "\x1b[1;32mship\x1b[0m \x1b[36m--help\x1b[0m"
+    );
+}
@@ -0,0 +1,77 @@
+use space_traders_cli::{
+    config::{self, Agent, Config},
+    survey_store::SurveyStore,
+    vault,
+};
+use tempfile::TempDir;
+
+#[test]
+fn profiles_are_saved_separately_and_legacy_tokens_move_to_the_vault() {
+    let dir = TempDir::new().unwrap();
+    std::env::set_var(config::CONFIG_DIR_ENV, dir.path());
+    std::env::set_var(vault::PASSPHRASE_ENV, "test passphrase");
+    std::fs::write(
+        dir.path().join("config.json"),
+        r#"{"account_token": "prod-token", "agents": [{"id": "PROD_AGENT", "token": "t"}]}"#,
+    )
+    .unwrap();
+
+    let prod = Config::load(config::DEFAULT_PROFILE).unwrap();
+    assert_eq!(prod.account_token, "prod-token");
+    assert_eq!(prod.agents.len(), 1);
+    assert_eq!(prod.agents[0].token, "t");
+    let stored = std::fs::read_to_string(dir.path().join("config.json")).unwrap();
+    assert!(!stored.contains("prod-token"));
+    let vault = std::fs::read_to_string(dir.path().join("vault.json")).unwrap();
+    assert!(!vault.contains("prod-token"));
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let metadata = std::fs::metadata(dir.path().join("vault.json")).unwrap();
+        assert_eq!(metadata.permissions().mode() & 0o777, 0o600);
+    }
+
+    let mut staging = Config::load("staging").unwrap();
+    assert!(staging.account_token.is_empty());
+    staging.account_token = String::from("staging-token");
+    staging.base_url = Some(String::from("http://localhost:8080"));
+    staging.agents.push(Agent {
+        id: String::from("STAGING_AGENT"),
+        token: String::from("s"),
+        faction: None,
+        reset_date: None,
+    });
+    staging.save().unwrap();
+
+    let prod = Config::load(config::DEFAULT_PROFILE).unwrap();
+    assert_eq!(prod.agents[0].id, "PROD_AGENT");
+    assert_eq!(prod.base_url, None);
+    assert_eq!(prod.account_token, "prod-token");
+    let staging = Config::load("staging").unwrap();
+    assert_eq!(staging.account_token, "staging-token");
+    assert_eq!(staging.agents[0].id, "STAGING_AGENT");
+    assert_eq!(staging.base_url.as_deref(), Some("http://localhost:8080"));
+
+    // A plaintext token left behind by an older build does not replace the vault's
+    let path = dir.path().join("config.json");
+    let mut stored: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+    stored["profiles"]["prod"]["account_token"] = serde_json::json!("old-token");
+    std::fs::write(&path, stored.to_string()).unwrap();
+    let prod = Config::load(config::DEFAULT_PROFILE).unwrap();
+    assert_eq!(prod.account_token, "prod-token");
+    assert!(!std::fs::read_to_string(&path)
+        .unwrap()
+        .contains("old-token"));
+
+    let mut surveys = SurveyStore::load("staging").unwrap();
+    surveys
+        .surveys
+        .insert(String::from("X1-TEST-A1"), Vec::new());
+    surveys.save().unwrap();
+    assert!(dir.path().join("profiles/staging/surveys.json").exists());
+    assert!(SurveyStore::load(config::DEFAULT_PROFILE)
+        .unwrap()
+        .surveys
+        .is_empty());
+}
@@ -0,0 +1,50 @@
+// Kept in its own test binary since the SIGINT sent here reaches the whole process
+#![cfg(unix)]
+
+mod common;
+
+use std::time::{Duration, Instant};
+
+use common::{application, config, start_server};
+use space_traders_cli::repl::{run_script, Interrupted, Session};
+use wiremock::{
+    matchers::{method, path},
+    Mock, ResponseTemplate,
+};
+
+fn send_interrupt_after(delay: Duration) {
+    tokio::spawn(async move {
+        tokio::time::sleep(delay).await;
+        std::process::Command::new("kill")
+            .args(["-INT", &std::process::id().to_string()])
+            .status()
+            .expect("Failed to send SIGINT");
+    });
+}
+
+#[tokio::test]
+async fn ctrl_c_cancels_every_command_not_just_the_first() {
+    let server = start_server().await;
+    Mock::given(method("POST"))
+        .and(path("/my/ships/MOCK_AGENT-1/orbit"))
+        .respond_with(ResponseTemplate::new(200).set_delay(Duration::from_secs(30)))
+        .with_priority(1)
+        .mount(&server)
+        .await;
+    let mut application = application(config(&server));
+    let script = tempfile::NamedTempFile::new().unwrap();
+    std::fs::write(
+        script.path(),
+        "ship orbit --callsign MOCK_AGENT --ship MOCK_AGENT-1\n",
+    )
+    .unwrap();
+
+    for _ in 0..2 {
+        let started = Instant::now();
+        send_interrupt_after(Duration::from_millis(500));
+        let result = run_script(&mut application, &mut Session::default(), script.path()).await;
+
+        assert!(result.unwrap_err().is::<Interrupted>());
+        assert!(started.elapsed() < Duration::from_secs(10));
+    }
+}
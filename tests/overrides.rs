@@ -0,0 +1,43 @@
+use space_traders_cli::config::{self, Config};
+use tempfile::TempDir;
+
+#[test]
+fn overridden_tokens_are_used_but_never_saved() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("ci-config.json");
+    config::set_config_path(path.clone());
+    assert_eq!(config::config_dir(), dir.path());
+    // Unlocking this would fail, every token below is overridden so it is never read
+    std::fs::write(dir.path().join("vault.json"), "locked").unwrap();
+    std::env::set_var(config::ACCOUNT_TOKEN_ENV, "env-account-token");
+    std::env::set_var(
+        format!("{}CI_AGENT", config::AGENT_TOKEN_ENV_PREFIX),
+        "env-agent-token",
+    );
+
+    let mut config = Config::load_with_overrides(config::DEFAULT_PROFILE, None).unwrap();
+    assert_eq!(config.account_token, "env-account-token");
+    assert_eq!(config.agents[0].id, "CI_AGENT");
+    assert_eq!(config.agents[0].token, "env-agent-token");
+    assert_eq!(
+        config.added_agent_name("CI-AGENT"),
+        Some(String::from("CI_AGENT"))
+    );
+    config.rename_added_agent("CI_AGENT", "CI-AGENT");
+    assert_eq!(config.agents[0].id, "CI-AGENT");
+    assert_eq!(config.added_agents(), vec![String::from("CI-AGENT")]);
+
+    let mut config =
+        Config::load_with_overrides(config::DEFAULT_PROFILE, Some("flag-account-token")).unwrap();
+    assert_eq!(config.account_token, "flag-account-token");
+    config.reset_date = Some(String::from("2024-01-01"));
+    config.save().unwrap();
+
+    let stored = std::fs::read_to_string(&path).unwrap();
+    assert!(stored.contains("2024-01-01"));
+    assert!(!stored.contains("CI_AGENT"));
+    assert_eq!(
+        std::fs::read_to_string(dir.path().join("vault.json")).unwrap(),
+        "locked"
+    );
+}
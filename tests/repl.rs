@@ -0,0 +1,37 @@
+use std::collections::BTreeMap;
+
+use space_traders_cli::repl::expand_alias;
+
+fn aliases() -> BTreeMap<String, String> {
+    BTreeMap::from([
+        (
+            String::from("mkt"),
+            String::from("system waypoint -s X1-AB -w X1-AB-C1 market"),
+        ),
+        (
+            String::from("go"),
+            String::from("ship navigate -c AGENT -s $1 --to $2"),
+        ),
+    ])
+}
+
+#[test]
+fn expands_aliases_and_appends_extra_arguments() {
+    assert_eq!(
+        expand_alias(&aliases(), "mkt --callsign AGENT").unwrap(),
+        "system waypoint -s X1-AB -w X1-AB-C1 market --callsign AGENT"
+    );
+    assert_eq!(
+        expand_alias(&aliases(), "ship list -c AGENT").unwrap(),
+        "ship list -c AGENT"
+    );
+}
+
+#[test]
+fn fills_placeholders_from_arguments() {
+    assert_eq!(
+        expand_alias(&aliases(), "go AGENT-1 X1-AB-C1").unwrap(),
+        "ship navigate -c AGENT -s AGENT-1 --to X1-AB-C1"
+    );
+    assert!(expand_alias(&aliases(), "go AGENT-1").is_err());
+}